@@ -0,0 +1,147 @@
+use std::time::{Duration, Instant};
+use pyo3::prelude::*;
+use pyo3::exceptions::PyValueError;
+
+/// Limits on a single search. The search stops as soon as any of the
+/// limits that are set is reached.
+#[derive(Clone, Debug)]
+pub struct Budget {
+    pub time: Option<Duration>,
+    pub iterations: Option<u64>,
+    pub rollouts: Option<u64>,
+    pub nodes: Option<usize>,
+}
+
+impl Budget {
+    pub fn new(
+        time_s: Option<f32>,
+        iterations: Option<u64>,
+        rollouts: Option<u64>,
+        nodes: Option<usize>,
+    ) -> PyResult<Self> {
+        if time_s.is_none() && iterations.is_none() && rollouts.is_none() && nodes.is_none() {
+            return Err(PyValueError::new_err("at least one search budget must be set"));
+        }
+        if iterations == Some(0) || rollouts == Some(0) || nodes == Some(0) {
+            return Err(PyValueError::new_err("search budgets must be greater than 0"));
+        }
+        let time = time_s
+            .map(|time_s| Duration::try_from_secs_f32(time_s)
+                .map_err(|_| PyValueError::new_err("time_budget_s must be a non-negative number")))
+            .transpose()?;
+        Ok(Self {
+            time,
+            iterations,
+            rollouts,
+            nodes,
+        })
+    }
+
+    /// Budget that never runs out, for searches that are stopped explicitly.
+    pub fn unlimited() -> Self {
+        Self {
+            time: None,
+            iterations: None,
+            rollouts: None,
+            nodes: None,
+        }
+    }
+
+    /// Budget for the `i`-th of `n` searches running side by side. Only the
+    /// time budget is shared, the counted budgets are divided between them
+    /// so that their shares add up to the whole.
+    pub fn split(&self, i: usize, n: usize) -> Self {
+        let share = |total: u64| total / n as u64 + u64::from((i as u64) < total % n as u64);
+        Self {
+            time: self.time,
+            iterations: self.iterations.map(share),
+            rollouts: self.rollouts.map(share),
+            nodes: self.nodes.map(|nodes| share(nodes as u64) as usize),
+        }
+    }
+
+    /// Iterations left before a counted budget runs out, `None` if no
+    /// counted budget is set. An iteration plays at most one rollout and
+    /// adds at most one node.
+    pub fn remaining(&self, info: &SearchInfo) -> Option<u64> {
+        [
+            self.iterations.map(|iterations| iterations.saturating_sub(info.iterations)),
+            self.rollouts.map(|rollouts| rollouts.saturating_sub(info.rollouts)),
+            self.nodes.map(|nodes| nodes.saturating_sub(info.nodes + info.dead_ends) as u64),
+        ].into_iter().flatten().min()
+    }
+
+    pub fn exhausted(&self, start: Instant, info: &SearchInfo) -> bool {
+        self.time.is_some_and(|time| start.elapsed() >= time)
+            || self.iterations.is_some_and(|iterations| info.iterations >= iterations)
+            || self.rollouts.is_some_and(|rollouts| info.rollouts >= rollouts)
+            || self.nodes.is_some_and(|nodes| info.nodes + info.dead_ends >= nodes)
+    }
+}
+
+/// Work done by the last search.
+#[pyclass]
+#[derive(Clone, Default, Debug)]
+pub struct SearchInfo {
+    #[pyo3(get)]
+    pub iterations: u64,
+    #[pyo3(get)]
+    pub rollouts: u64,
+    /// Nodes added to the tree.
+    #[pyo3(get)]
+    pub nodes: usize,
+    /// Iterations that ended at a finished game or a proven position and so
+    /// added no node. They count against the node budget, which otherwise
+    /// could not run out once the whole game tree has been added.
+    pub dead_ends: usize,
+    /// Positions reached by a new move order that were already in the tree
+    /// and so were merged instead of added.
+    #[pyo3(get)]
+    pub transpositions: u64,
+    /// Deepest leaf reached by an iteration.
+    #[pyo3(get)]
+    pub max_depth: u32,
+    /// Sum of the depths of the leaves reached by all iterations.
+    pub depth_sum: u64,
+    #[pyo3(get)]
+    pub elapsed_s: f32,
+    /// Result proven by the MCTS solver for the player to move at the root:
+    /// "win", "loss" or "draw".
+    #[pyo3(get)]
+    pub proof: Option<&'static str>,
+}
+
+impl std::ops::AddAssign for SearchInfo {
+    fn add_assign(&mut self, other: Self) {
+        self.iterations += other.iterations;
+        self.rollouts += other.rollouts;
+        self.nodes += other.nodes;
+        self.dead_ends += other.dead_ends;
+        self.transpositions += other.transpositions;
+        self.max_depth = self.max_depth.max(other.max_depth);
+        self.depth_sum += other.depth_sum;
+        self.elapsed_s = self.elapsed_s.max(other.elapsed_s);
+        self.proof = self.proof.or(other.proof);
+    }
+}
+
+#[pymethods]
+impl SearchInfo {
+    /// Mean depth of the leaves reached by the iterations.
+    #[getter]
+    pub fn avg_depth(&self) -> f32 {
+        if self.iterations == 0 { 0.0 } else { self.depth_sum as f32 / self.iterations as f32 }
+    }
+
+    #[getter]
+    pub fn rollouts_per_s(&self) -> f32 {
+        if self.elapsed_s > 0.0 { self.rollouts as f32 / self.elapsed_s } else { 0.0 }
+    }
+
+    fn __repr__(&self) -> String {
+        format!("SearchInfo(iterations={}, rollouts={}, nodes={}, transpositions={}, max_depth={}, \
+            avg_depth={:.2}, elapsed_s={:.3}, proof={})",
+            self.iterations, self.rollouts, self.nodes, self.transpositions, self.max_depth,
+            self.avg_depth(), self.elapsed_s, self.proof.unwrap_or("None"))
+    }
+}
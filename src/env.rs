@@ -26,7 +26,7 @@ pub fn action_to_move(a: u8) -> Move {
     ALL_MOVES[a as usize]
 }
 
-fn create_observation(game: &Game) -> [[[u8; 9]; 9]; 3] {
+pub fn create_observation(game: &Game) -> [[[u8; 9]; 9]; 3] {
     // observation is a 3x9x9 tensor
     // first layer contains the current player's marks
     // second layer contains the opponent's marks
@@ -37,14 +37,12 @@ fn create_observation(game: &Game) -> [[[u8; 9]; 9]; 3] {
 
     for x in 0..9 {
         for y in 0..9 {
-            let field = board[x][y];
-            match field {
-                Some(player) => if player == Player::X {
+            if let Some(player) = board[x][y] {
+                if player == Player::X {
                     observation[0][x][y] = 1
                 } else {
                     observation[1][x][y] = 1
                 }
-                None => {}
             }
         }
     }
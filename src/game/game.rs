@@ -1,4 +1,4 @@
-use super::{BigBoard, Player, Move};
+use super::{BigBoard, SmallBoard, Player, Move, KEYS};
 
 #[derive(Clone)]
 pub struct Game {
@@ -28,6 +28,14 @@ impl Game {
         self.board.is_over()
     }
 
+    pub fn last_move(&self) -> Option<Move> {
+        self.last_move
+    }
+
+    pub fn small_board(&self, at: (u8, u8)) -> &SmallBoard {
+        self.board.at(at)
+    }
+
     pub fn make_move(&mut self, move_: Move) {
         self.board.place(self.current_player, move_);
         self.current_player = self.current_player.other();
@@ -47,6 +55,38 @@ impl Game {
         }
     }
 
+    /// Zobrist hash of the position: the marks on the board, the small board
+    /// the next move is forced into and the player to move. Positions reached
+    /// by different move orders hash the same.
+    pub fn hash(&self) -> u64 {
+        let mut hash = 0;
+        let mut cell = 0;
+        for i in 0..3 {
+            for j in 0..3 {
+                for k in 0..3 {
+                    for l in 0..3 {
+                        match self.board.at((i, j)).at((k, l)) {
+                            Some(Player::X) => hash ^= KEYS.cells[cell][0],
+                            Some(Player::O) => hash ^= KEYS.cells[cell][1],
+                            None => {}
+                        }
+                        cell += 1;
+                    }
+                }
+            }
+        }
+        let forced = match self.last_move {
+            Some(last_move) if !self.board.at(last_move.1).is_over() =>
+                (last_move.1.0 * 3 + last_move.1.1) as usize,
+            _ => 9,
+        };
+        hash ^= KEYS.forced[forced];
+        if self.current_player == Player::O {
+            hash ^= KEYS.o_to_move;
+        }
+        hash
+    }
+
     pub fn board(&self) -> [[Option<Player>; 9]; 9] {
         let mut board = [[None; 9]; 9];
         board.iter_mut().flatten().zip(FORMAT_ORDER.iter())
@@ -236,3 +276,53 @@ const FORMAT_ORDER: &[(u8, u8, u8, u8)] = &[
     (2, 2, 2, 1),
     (2, 2, 2, 2),
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(moves: &[Move]) -> Game {
+        let mut game = Game::new();
+        for &move_ in moves {
+            assert!(game.move_valid(move_));
+            game.make_move(move_);
+        }
+        game
+    }
+
+    #[test]
+    fn hash_ignores_move_order() {
+        let a = Move((0, 0), (1, 1));
+        let b = Move((1, 1), (0, 0));
+        let c = Move((0, 0), (2, 2));
+        let d = Move((2, 2), (0, 0));
+        assert_eq!(play(&[a, b, c, d]).hash(), play(&[c, d, a, b]).hash());
+    }
+
+    #[test]
+    fn hash_depends_on_forced_board() {
+        // the same marks with the next move forced into different boards
+        let first = play(&[
+            Move((0, 0), (1, 1)),
+            Move((1, 1), (1, 1)),
+            Move((1, 1), (0, 0)),
+            Move((0, 0), (0, 0)),
+        ]);
+        let second = play(&[
+            Move((1, 1), (0, 0)),
+            Move((0, 0), (0, 0)),
+            Move((0, 0), (1, 1)),
+            Move((1, 1), (1, 1)),
+        ]);
+        assert!(first.board() == second.board());
+        assert_ne!(first.hash(), second.hash());
+    }
+
+    #[test]
+    fn hash_depends_on_player_to_move() {
+        let game = play(&[Move((0, 0), (1, 1)), Move((1, 1), (0, 0))]);
+        let mut flipped = game.clone();
+        flipped.current_player = Player::O;
+        assert_ne!(game.hash(), flipped.hash());
+    }
+}
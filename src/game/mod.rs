@@ -1,15 +1,18 @@
 mod sb;
 mod bb;
 mod player;
+#[allow(clippy::module_inception)]
 mod game;
 mod utils;
+mod zobrist;
 
 pub use sb::SmallBoard;
 pub use bb::BigBoard;
 pub use player::Player;
 pub use game::Game;
 
-use utils::get_winner;
+pub use utils::get_winner;
+use zobrist::KEYS;
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct Move(pub (u8, u8), pub (u8, u8));
@@ -0,0 +1,33 @@
+use once_cell::sync::Lazy;
+
+/// Random keys for Zobrist hashing, generated from a fixed seed so that a
+/// position hashes the same in every run.
+pub struct Keys {
+    /// One key per cell and player.
+    pub cells: [[u64; 2]; 81],
+    /// One key per small board the next move is forced into, the last one
+    /// for a free choice.
+    pub forced: [u64; 10],
+    pub o_to_move: u64,
+}
+
+pub static KEYS: Lazy<Keys> = Lazy::new(|| {
+    // splitmix64
+    let mut state: u64 = 0x5555_5555_5555_5555;
+    let mut next = || {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    };
+    let mut keys = Keys {
+        cells: [[0; 2]; 81],
+        forced: [0; 10],
+        o_to_move: 0,
+    };
+    keys.cells.iter_mut().flatten().for_each(|key| *key = next());
+    keys.forced.iter_mut().for_each(|key| *key = next());
+    keys.o_to_move = next();
+    keys
+});
@@ -1,17 +1,30 @@
 mod game;
+mod atomic;
+mod budget;
+mod rng;
+mod rollout;
+mod value;
 mod env;
 mod mcts;
 mod pmcts;
 
 use pyo3::prelude::*;
 use env::UTTTEnvImpl;
-use mcts::MCTS;
+use mcts::{MCTS, SearchHandle, SearchStats, ChildStats, WdlStats, PvLine, TreeNode};
 use pmcts::PMCTS;
+use budget::SearchInfo;
 
 #[pymodule]
 fn uttt_mcts(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<UTTTEnvImpl>()?;
     m.add_class::<MCTS>()?;
+    m.add_class::<SearchHandle>()?;
     m.add_class::<PMCTS>()?;
+    m.add_class::<SearchInfo>()?;
+    m.add_class::<SearchStats>()?;
+    m.add_class::<ChildStats>()?;
+    m.add_class::<WdlStats>()?;
+    m.add_class::<PvLine>()?;
+    m.add_class::<TreeNode>()?;
     Ok(())
 }
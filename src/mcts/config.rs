@@ -0,0 +1,224 @@
+use std::sync::Arc;
+use pyo3::prelude::*;
+use pyo3::exceptions::PyValueError;
+use crate::rollout::{RolloutPolicy, Uniform};
+use crate::value::{ValueFunction, Heuristic};
+use super::evaluator::{Evaluator, RolloutEvaluator};
+
+/// Rule used to pick the move played once the search is over.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FinalSelection {
+    /// Child with the most visits.
+    Robust,
+    /// Child with the highest mean reward.
+    Max,
+    /// Child with the highest lower confidence bound.
+    Secure,
+}
+
+impl FinalSelection {
+    pub fn from_name(name: &str) -> PyResult<Self> {
+        match name {
+            "robust" => Ok(FinalSelection::Robust),
+            "max" => Ok(FinalSelection::Max),
+            "secure" => Ok(FinalSelection::Secure),
+            _ => Err(PyValueError::new_err(format!(
+                "unknown final selection '{}', expected 'robust', 'max' or 'secure'", name))),
+        }
+    }
+}
+
+/// Order in which the unexpanded children of a node are added to the tree.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ExpansionOrder {
+    /// Uniformly random unexpanded child.
+    Random,
+    /// First unexpanded child in action order.
+    Ordered,
+}
+
+impl ExpansionOrder {
+    pub fn from_name(name: &str) -> PyResult<Self> {
+        match name {
+            "random" => Ok(ExpansionOrder::Random),
+            "ordered" => Ok(ExpansionOrder::Ordered),
+            _ => Err(PyValueError::new_err(format!(
+                "unknown expansion order '{}', expected 'random' or 'ordered'", name))),
+        }
+    }
+}
+
+/// Formula used to choose the child to descend into.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Selection {
+    /// UCB1 on the mean reward, with a rollout from every new leaf.
+    Uct,
+    /// Mean reward plus an exploration term weighted by the prior of the
+    /// move, with the leaves scored by an evaluator.
+    Puct,
+}
+
+impl Selection {
+    pub fn from_name(name: &str) -> PyResult<Self> {
+        match name {
+            "uct" => Ok(Selection::Uct),
+            "puct" => Ok(Selection::Puct),
+            _ => Err(PyValueError::new_err(format!(
+                "unknown selection '{}', expected 'uct' or 'puct'", name))),
+        }
+    }
+}
+
+/// How the search threads divide the work.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Parallelism {
+    /// Every thread grows its own tree and the root statistics are merged.
+    Root,
+    /// All threads grow one shared tree.
+    Tree,
+}
+
+impl Parallelism {
+    pub fn from_name(name: &str) -> PyResult<Self> {
+        match name {
+            "root" => Ok(Parallelism::Root),
+            "tree" => Ok(Parallelism::Tree),
+            _ => Err(PyValueError::new_err(format!(
+                "unknown parallelism '{}', expected 'root' or 'tree'", name))),
+        }
+    }
+}
+
+/// Schedule for the weight of the AMAF value in RAVE, which starts at 1
+/// and decays as the node's own visits grow.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Rave {
+    /// `sqrt(k / (3n + k))`, where `k` is the number of visits at which
+    /// both values get equal weight.
+    Equivalence(f32),
+    /// Minimum mean squared error schedule with the given AMAF bias.
+    MinMse(f32),
+}
+
+impl Rave {
+    pub fn from_name(name: Option<&str>, equivalence: f32, bias: f32) -> PyResult<Option<Self>> {
+        if !(equivalence.is_finite() && equivalence > 0.0) {
+            return Err(PyValueError::new_err("rave_equivalence must be a finite number greater than 0"));
+        }
+        if !bias.is_finite() {
+            return Err(PyValueError::new_err("rave_bias must be a finite number"));
+        }
+        match name {
+            None => Ok(None),
+            Some("equivalence") => Ok(Some(Rave::Equivalence(equivalence))),
+            Some("min_mse") => Ok(Some(Rave::MinMse(bias))),
+            Some(name) => Err(PyValueError::new_err(format!(
+                "unknown RAVE schedule '{}', expected 'equivalence' or 'min_mse'", name))),
+        }
+    }
+
+    /// Weight of the AMAF mean for a node with `visits` visits and
+    /// `amaf_visits` AMAF visits.
+    pub fn beta(self, visits: u32, amaf_visits: u32) -> f32 {
+        let (n, amaf_n) = (visits as f32, amaf_visits as f32);
+        match self {
+            Rave::Equivalence(k) => (k / (3.0 * n + k)).sqrt(),
+            Rave::MinMse(b) => amaf_n / (n + amaf_n + 4.0 * b * b * n * amaf_n),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MctsConfig {
+    /// Exploration constant of the UCT or PUCT formula.
+    pub exploration: f32,
+    /// Reward credited to both players for a drawn rollout.
+    pub draw_value: f32,
+    pub final_selection: FinalSelection,
+    /// Value assumed for unvisited children during selection.
+    /// `None` expands every child before any of them is revisited, or with
+    /// PUCT assumes the value of the parent.
+    pub first_play_urgency: Option<f32>,
+    pub expansion_order: ExpansionOrder,
+    /// Largest number of nodes the tree may grow to. A search on a node
+    /// budget ends once the tree is full.
+    pub max_nodes: Option<usize>,
+    /// Largest approximate size of the tree in bytes.
+    pub max_memory_bytes: Option<usize>,
+    /// Losses added to every node on a path that is being searched by
+    /// another thread of a shared tree.
+    pub virtual_loss: u32,
+    /// Back up proven wins, losses and draws through the tree.
+    pub solver: bool,
+    /// Blend AMAF statistics into the UCT values, `None` disables RAVE.
+    pub rave: Option<Rave>,
+    /// Store every position once, whatever the move order reaching it.
+    pub transpositions: bool,
+    /// Chooses the moves of rollouts: "uniform", "decisive", "anti_decisive",
+    /// "small_board", "mast" or "lgr".
+    pub rollout_policy: Arc<dyn RolloutPolicy>,
+    /// Keep what an adaptive rollout policy learned when the root moves.
+    pub keep_rollout_tables: bool,
+    /// Plies after which a rollout stops and the position is scored by
+    /// `value_function`, 0 at the leaf, `None` plays to the end of the game.
+    pub rollout_depth: Option<u32>,
+    /// Scores the position where a truncated rollout stops: "heuristic" or
+    /// a callable taking the observation of the position and returning the
+    /// expected reward of the player to move.
+    pub value_function: Arc<dyn ValueFunction>,
+    /// Concentration of the Dirichlet noise mixed in at the root, `None`
+    /// for no noise.
+    pub dirichlet_alpha: Option<f32>,
+    /// Weight of the root noise.
+    pub dirichlet_epsilon: f32,
+    /// "uct", or "puct" to choose children by their mean plus `exploration`
+    /// times their prior times the square root of the parent's visits over
+    /// one plus their own.
+    pub selection: Selection,
+    /// Scores the leaves when `selection` is PUCT: "rollout", uniform priors
+    /// and a rollout, "heuristic", uniform priors and the heuristic value,
+    /// or a callable taking a batch of observations and legal action masks
+    /// and returning priors and values.
+    pub evaluator: Arc<dyn Evaluator>,
+    /// Leaves collected under virtual loss before they are scored by one
+    /// call of the evaluator.
+    pub batch_size: usize,
+}
+
+impl MctsConfig {
+    /// Configuration for one of `n` trees sharing the tree size limits.
+    pub fn split(&self, n: usize) -> Self {
+        Self {
+            max_nodes: self.max_nodes.map(|max| max.div_ceil(n)),
+            max_memory_bytes: self.max_memory_bytes.map(|max| max.div_ceil(n)),
+            ..self.clone()
+        }
+    }
+}
+
+impl Default for MctsConfig {
+    fn default() -> Self {
+        Self {
+            exploration: 1.41,
+            draw_value: 0.5,
+            final_selection: FinalSelection::Robust,
+            first_play_urgency: None,
+            expansion_order: ExpansionOrder::Random,
+            max_nodes: None,
+            max_memory_bytes: None,
+            virtual_loss: 1,
+            solver: true,
+            rave: None,
+            transpositions: false,
+            rollout_policy: Arc::new(Uniform),
+            keep_rollout_tables: true,
+            rollout_depth: None,
+            value_function: Arc::new(Heuristic),
+            dirichlet_alpha: None,
+            dirichlet_epsilon: 0.25,
+            selection: Selection::Uct,
+            evaluator: Arc::new(RolloutEvaluator),
+            batch_size: 1,
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use std::fmt::Debug;
+use std::sync::Arc;
+use pyo3::prelude::*;
+use pyo3::exceptions::PyValueError;
+use rand::rngs::StdRng;
+use crate::env::{create_observation, move_to_action};
+use crate::game::{Game, Move, Player};
+use crate::rollout::rollout;
+use crate::value::{ValueFunction, Heuristic};
+use super::config::MctsConfig;
+use super::node::Wdl;
+
+/// Scores a leaf of a PUCT search: how promising each move is and how the
+/// position stands.
+pub trait Evaluator: Send + Sync + Debug {
+    /// Prior probabilities of the valid moves of `state`, in the order of
+    /// `valid_moves` and summing to 1, and the expected result of the
+    /// player to move. Fails if a Python callable does.
+    fn evaluate(&self, state: &Game, config: &MctsConfig, rng: &mut StdRng) -> PyResult<(Vec<f32>, Wdl)>;
+
+    /// Evaluates several positions at once, by default one after another.
+    fn evaluate_batch(&self, states: &[Game], config: &MctsConfig, rng: &mut StdRng) -> PyResult<Vec<(Vec<f32>, Wdl)>> {
+        states.iter().map(|state| self.evaluate(state, config, rng)).collect()
+    }
+}
+
+fn uniform(state: &Game) -> Vec<f32> {
+    let n = state.valid_moves().len();
+    vec![1.0 / n as f32; n]
+}
+
+/// Uniform priors and the result of a rollout played with the configured
+/// rollout policy, depth and value function.
+#[derive(Debug)]
+pub struct RolloutEvaluator;
+
+impl Evaluator for RolloutEvaluator {
+    fn evaluate(&self, state: &Game, config: &MctsConfig, rng: &mut StdRng) -> PyResult<(Vec<f32>, Wdl)> {
+        // the last move lets a reply policy learn the reply to it
+        let mut line: Vec<Move> = state.last_move().into_iter().collect();
+        let first = if line.is_empty() { state.current_player() } else { state.current_player().other() };
+        let result = simulate(state.clone(), &mut line, &mut Vec::new(), first, config, rng)?;
+        Ok((uniform(state), result))
+    }
+}
+
+/// Result of a rollout from `state` for the player to move there, with the
+/// configured rollout policy, depth and value function. The moves played
+/// are put in `playout`. A rollout played to the end of the game is learned
+/// from by an adaptive rollout policy, as the continuation of `line`, the
+/// moves leading to `state` starting with a move of `first`. Fails if the
+/// value function does.
+pub fn simulate(
+    state: Game,
+    line: &mut Vec<Move>,
+    playout: &mut Vec<Move>,
+    first: Player,
+    config: &MctsConfig,
+    rng: &mut StdRng,
+) -> PyResult<Wdl> {
+    let player = state.current_player();
+    let end = rollout(state, &*config.rollout_policy, rng, playout, config.rollout_depth);
+    if !end.is_over() {
+        // truncated rollout
+        let value = config.value_function.value(&end)?;
+        return Ok(Wdl::value(if end.current_player() == player { value } else { 1.0 - value }));
+    }
+    let winner = end.winner();
+    line.extend(playout.iter());
+    config.rollout_policy.update(line, first, winner);
+    Ok(match winner {
+        None => Wdl::DRAW,
+        Some(winner) => if winner == player { Wdl::WIN } else { Wdl::LOSS },
+    })
+}
+
+/// Uniform priors and the heuristic value of the position itself.
+#[derive(Debug)]
+pub struct HeuristicEvaluator;
+
+impl Evaluator for HeuristicEvaluator {
+    fn evaluate(&self, state: &Game, _config: &MctsConfig, _rng: &mut StdRng) -> PyResult<(Vec<f32>, Wdl)> {
+        Ok((uniform(state), Wdl::value(Heuristic.value(state)?)))
+    }
+}
+
+/// Python callable taking a list of observations, as returned by
+/// `UTTTEnvImpl.step`, and a list of masks of the legal actions, and
+/// returning the prior probabilities of the 81 actions and the expected
+/// reward, between 0 and 1, of the player to move for every position.
+/// Any iterables of numbers will do, e.g. NumPy arrays or PyTorch tensors.
+/// The priors of illegal actions are dropped and the rest renormalized.
+/// Output of the wrong shape or a value that is not between 0 and 1 raises
+/// `ValueError`. The GIL is taken for every batch.
+#[derive(Debug)]
+pub struct PyEvaluator {
+    function: PyObject,
+}
+
+impl PyEvaluator {
+    fn call(&self, states: &[Game]) -> PyResult<(Vec<Vec<f32>>, Vec<f32>)> {
+        let observations: Vec<_> = states.iter().map(create_observation).collect();
+        let masks: Vec<[bool; 81]> = states.iter()
+            .map(|state| {
+                let mut mask = [false; 81];
+                for m in state.valid_moves() {
+                    mask[move_to_action(m) as usize] = true;
+                }
+                mask
+            })
+            .collect();
+        Python::with_gil(|py| {
+            let output = self.function.call1(py, (observations, masks))?;
+            let (priors, values): (&PyAny, &PyAny) = output.extract(py)?;
+            let priors = priors.iter()?
+                .map(|row| row?.iter()?.map(|prior| prior?.extract()).collect())
+                .collect::<PyResult<_>>()?;
+            let values = values.iter()?.map(|value| value?.extract()).collect::<PyResult<_>>()?;
+            Ok((priors, values))
+        })
+    }
+}
+
+impl Evaluator for PyEvaluator {
+    fn evaluate(&self, state: &Game, config: &MctsConfig, rng: &mut StdRng) -> PyResult<(Vec<f32>, Wdl)> {
+        Ok(self.evaluate_batch(std::slice::from_ref(state), config, rng)?.pop().unwrap())
+    }
+
+    fn evaluate_batch(&self, states: &[Game], _config: &MctsConfig, _rng: &mut StdRng) -> PyResult<Vec<(Vec<f32>, Wdl)>> {
+        if states.is_empty() {
+            return Ok(Vec::new());
+        }
+        let (priors, values) = self.call(states)?;
+        if priors.len() != states.len() || values.len() != states.len() {
+            return Err(PyValueError::new_err(format!(
+                "evaluator returned {} priors and {} values for {} positions",
+                priors.len(), values.len(), states.len())));
+        }
+        states.iter()
+            .zip(priors)
+            .zip(values)
+            .map(|((state, priors), value)| {
+                if priors.len() != 81 {
+                    return Err(PyValueError::new_err(format!(
+                        "evaluator returned {} priors instead of 81", priors.len())));
+                }
+                if !(0.0..=1.0).contains(&value) {
+                    return Err(PyValueError::new_err(format!(
+                        "evaluator returned value {}, expected a number between 0 and 1", value)));
+                }
+                // NaN priors count as 0
+                let legal: Vec<f32> = state.valid_moves()
+                    .into_iter()
+                    .map(|m| priors[move_to_action(m) as usize].max(0.0))
+                    .collect();
+                let total: f32 = legal.iter().sum();
+                let legal = if total > 0.0 && total.is_finite() {
+                    legal.iter().map(|p| p / total).collect()
+                } else {
+                    uniform(state)
+                };
+                Ok((legal, Wdl::value(value)))
+            })
+            .collect()
+    }
+}
+
+/// Evaluator given from Python: "rollout", "heuristic" or a callable.
+/// `None` is the rollout evaluator.
+pub fn from_py(evaluator: Option<&PyAny>) -> PyResult<Arc<dyn Evaluator>> {
+    match evaluator {
+        None => Ok(Arc::new(RolloutEvaluator)),
+        Some(function) if function.is_callable() => Ok(Arc::new(PyEvaluator { function: function.into() })),
+        Some(name) => match name.extract::<&str>() {
+            Ok("rollout") => Ok(Arc::new(RolloutEvaluator)),
+            Ok("heuristic") => Ok(Arc::new(HeuristicEvaluator)),
+            _ => Err(PyValueError::new_err(format!(
+                "unknown evaluator {}, expected 'rollout', 'heuristic' or a callable", name))),
+        },
+    }
+}
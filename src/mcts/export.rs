@@ -0,0 +1,112 @@
+use std::fmt::Write;
+use crate::env::move_to_action;
+use super::config::MctsConfig;
+use super::tree::Tree;
+
+/// Which nodes of a tree to export.
+pub struct Filter {
+    /// Deepest ply below the root to include, `None` for all.
+    pub max_depth: Option<u32>,
+    /// Nodes with fewer visits are left out along with their subtrees.
+    pub min_visits: u32,
+}
+
+/// Expanded children of `n` that pass `filter` at `depth`, with the index
+/// of their edge.
+fn children(tree: &Tree, n: usize, depth: u32, filter: &Filter) -> Vec<(usize, usize)> {
+    if filter.max_depth.is_some_and(|max| depth >= max) {
+        return Vec::new();
+    }
+    tree.nodes[n].children
+        .iter()
+        .enumerate()
+        .filter_map(|(i, &(_, child))| child.map(|child| (i, child)))
+        .filter(|&(_, child)| {
+            let visits = tree.nodes[child].visits();
+            visits > 0 && visits >= filter.min_visits
+        })
+        .collect()
+}
+
+/// Mean reward of the player who moved into `n`, `None` if unvisited.
+fn value(tree: &Tree, n: usize, config: &MctsConfig) -> Option<f32> {
+    let node = &tree.nodes[n];
+    (node.visits() > 0).then(|| node.reward(config.draw_value) / node.visits() as f32)
+}
+
+fn json_number(value: Option<f32>) -> String {
+    value.map_or("null".to_string(), |value| format!("{}", value))
+}
+
+/// The tree below the root as nested JSON objects. A node reached by
+/// several move orders appears once under every parent.
+pub fn to_json(tree: &Tree, config: &MctsConfig, filter: &Filter) -> String {
+    let mut json = String::new();
+    write_json(&mut json, tree, config, filter, tree.root, None, 0);
+    json
+}
+
+fn write_json(
+    json: &mut String,
+    tree: &Tree,
+    config: &MctsConfig,
+    filter: &Filter,
+    n: usize,
+    edge: Option<(usize, usize)>,
+    depth: u32,
+) {
+    let node = &tree.nodes[n];
+    let (action, uct) = match edge {
+        Some((parent, i)) => {
+            let parent = &tree.nodes[parent];
+            let uct = (parent.visits() > 0).then(|| parent.child_uct_value(i, node, config));
+            (Some(move_to_action(parent.children[i].0)), uct)
+        }
+        None => (None, None),
+    };
+    write!(json, "{{\"action\":{},\"visits\":{},\"value\":{},\"uct\":{},\"proof\":{},\"children\":[",
+        action.map_or("null".to_string(), |action| action.to_string()),
+        node.visits(),
+        json_number(value(tree, n, config)),
+        json_number(uct),
+        node.proof().name().map_or("null".to_string(), |proof| format!("\"{}\"", proof)),
+    ).unwrap();
+    for (k, (i, child)) in children(tree, n, depth, filter).into_iter().enumerate() {
+        if k > 0 {
+            json.push(',');
+        }
+        write_json(json, tree, config, filter, child, Some((n, i)), depth + 1);
+    }
+    json.push_str("]}");
+}
+
+/// The tree below the root as a Graphviz digraph. Nodes are labelled with
+/// their visits and value, edges with their move and UCT score. A node
+/// reached by several move orders appears once.
+pub fn to_dot(tree: &Tree, config: &MctsConfig, filter: &Filter) -> String {
+    let mut dot = String::from("digraph mcts {\n    node [shape=box];\n");
+    let mut depths = vec![None; tree.size()];
+    depths[tree.root] = Some(0);
+    let mut queue = vec![tree.root];
+    let mut k = 0;
+    while k < queue.len() {
+        let n = queue[k];
+        let node = &tree.nodes[n];
+        let depth = depths[n].unwrap();
+        let value = value(tree, n, config).map_or("-".to_string(), |value| format!("{:.3}", value));
+        let proof = node.proof().name().map_or(String::new(), |proof| format!("\\n{}", proof));
+        writeln!(dot, "    n{} [label=\"visits {}\\nvalue {}{}\"];", n, node.visits(), value, proof).unwrap();
+        for (i, child) in children(tree, n, depth, filter) {
+            let uct = node.child_uct_value(i, &tree.nodes[child], config);
+            writeln!(dot, "    n{} -> n{} [label=\"{}\\nuct {:.3}\"];",
+                n, child, move_to_action(node.children[i].0), uct).unwrap();
+            if depths[child].is_none() {
+                depths[child] = Some(depth + 1);
+                queue.push(child);
+            }
+        }
+        k += 1;
+    }
+    dot.push_str("}\n");
+    dot
+}
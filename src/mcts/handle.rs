@@ -0,0 +1,89 @@
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use pyo3::prelude::*;
+use pyo3::exceptions::PyValueError;
+use crate::env::move_to_action;
+use crate::budget::SearchInfo;
+use super::config::MctsConfig;
+use super::root::{best_root_child, root_value, root_visits};
+use super::search::{SearchResult, Status};
+use super::tree::Tree;
+
+/// Handle to a search started with `MCTS.start_search`. The search runs in
+/// the background until its budget is exhausted or it is cancelled, and
+/// can be awaited from asyncio.
+#[pyclass]
+pub struct SearchHandle {
+    pub status: Arc<Status>,
+    pub trees: Arc<[RwLock<Tree>]>,
+    pub config: MctsConfig,
+}
+
+#[pymethods]
+impl SearchHandle {
+    pub fn done(&self) -> bool {
+        self.status.result().is_some()
+    }
+
+    /// Stops the search after the current iterations. Does not wait for it.
+    pub fn cancel(&self) {
+        self.status.cancel();
+    }
+
+    /// Action the search would choose now, or the chosen one once it is done.
+    pub fn best_action(&self) -> Option<u8> {
+        let result = self.status.result();
+        match &*result {
+            Some(result) => result.best,
+            None => best_root_child(&self.trees, &self.config).map(|child| child.action),
+        }.map(move_to_action)
+    }
+
+    /// Root value so far, `None` until the root has been visited.
+    pub fn root_value(&self) -> Option<f32> {
+        (root_visits(&self.trees) > 0).then(|| root_value(&self.trees, self.config.draw_value))
+    }
+
+    /// Iterations completed so far.
+    pub fn iterations(&self) -> u64 {
+        self.status.iterations()
+    }
+
+    pub fn elapsed_s(&self) -> f32 {
+        match &*self.status.result() {
+            Some(result) => result.info.elapsed_s,
+            None => self.status.elapsed().as_secs_f32(),
+        }
+    }
+
+    /// Work done by the search once it is done.
+    pub fn info(&self) -> Option<SearchInfo> {
+        self.status.result().as_ref().map(|result| result.info.clone())
+    }
+
+    /// Blocks until the search is done and returns the chosen action.
+    /// Returns `None` if the timeout passes first. The GIL is released
+    /// while waiting. Raises the exception that ended the search, if any.
+    #[pyo3(signature = (timeout_s = None))]
+    pub fn wait(&self, py: Python, timeout_s: Option<f32>) -> PyResult<Option<u8>> {
+        // an infinite timeout waits like no timeout
+        let timeout = timeout_s
+            .filter(|&timeout_s| timeout_s != f32::INFINITY)
+            .map(|timeout_s| Duration::try_from_secs_f32(timeout_s)
+                .map_err(|_| PyValueError::new_err("timeout_s must be a non-negative number")))
+            .transpose()?;
+        match py.allow_threads(|| self.status.wait(timeout)) {
+            Some(SearchResult { error: Some(err), .. }) => Err(err.clone_ref(py)),
+            result => Ok(result.and_then(|result| result.best).map(move_to_action)),
+        }
+    }
+
+    /// Waits in the default executor of the running event loop.
+    fn __await__(slf: &PyCell<Self>) -> PyResult<PyObject> {
+        let py = slf.py();
+        let future = py.import("asyncio")?
+            .call_method0("get_running_loop")?
+            .call_method1("run_in_executor", (py.None(), slf.getattr("wait")?))?;
+        Ok(future.call_method0("__await__")?.into())
+    }
+}
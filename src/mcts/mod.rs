@@ -0,0 +1,458 @@
+mod config;
+mod evaluator;
+mod export;
+mod handle;
+mod node;
+mod pv;
+mod root;
+mod search;
+mod stats;
+mod tree;
+mod tree_node;
+
+use std::sync::{Arc, RwLock};
+use rand::SeedableRng;
+use rand::distributions::Distribution;
+use rand_distr::Gamma;
+use rand::rngs::StdRng;
+use pyo3::prelude::*;
+use pyo3::exceptions::{PyValueError, PyRuntimeError};
+use crate::env::{UTTTEnvImpl, action_to_move, move_to_action};
+use crate::game::Game;
+use crate::budget::{Budget, SearchInfo};
+
+pub use config::{MctsConfig, FinalSelection, ExpansionOrder, Parallelism, Rave, Selection};
+pub use handle::SearchHandle;
+pub use stats::{SearchStats, ChildStats, WdlStats};
+pub use pv::PvLine;
+pub use tree_node::TreeNode;
+use root::{best_root_child, sample_root_child, root_policy, root_proof, root_value, root_visits, root_wdl};
+use search::{Worker, Background, SearchResult, Status};
+use tree::Tree;
+
+/// Monte Carlo tree search. With more than one thread, either every thread
+/// grows its own tree from the root position and the root statistics of all
+/// trees are merged to choose the move, or all threads share one tree.
+/// The search can also run in the background, either between moves or on
+/// the budget through `start_search`. The options are described on
+/// `MctsConfig`.
+#[pyclass]
+#[allow(clippy::upper_case_acronyms)]
+pub struct MCTS {
+    trees: Arc<[RwLock<Tree>]>,
+    /// Empty while the workers are searching in the background.
+    workers: Vec<Worker>,
+    background: Option<Background>,
+    root_state: Game,
+    budget: Budget,
+    config: MctsConfig,
+    last_search: SearchInfo,
+    /// Samples the played move when `run` is given a temperature.
+    rng: StdRng,
+}
+
+#[pymethods]
+impl MCTS {
+    #[new]
+    #[pyo3(signature = (
+        env,
+        time_budget_s = None,
+        *,
+        iteration_budget = None,
+        rollout_budget = None,
+        node_budget = None,
+        exploration = 1.41,
+        draw_value = 0.5,
+        final_selection = "robust",
+        first_play_urgency = None,
+        expansion_order = "random",
+        max_nodes = None,
+        max_memory_bytes = None,
+        threads = 1,
+        parallel = "root",
+        virtual_loss = 1,
+        solver = true,
+        rave = None,
+        rave_equivalence = 1000.0,
+        rave_bias = 0.1,
+        transpositions = false,
+        rollout_policy = "uniform",
+        rollout_epsilon = 0.0,
+        rollout_temperature = 0.1,
+        keep_rollout_tables = true,
+        rollout_depth = None,
+        value_function = None,
+        dirichlet_alpha = None,
+        dirichlet_epsilon = 0.25,
+        selection = "uct",
+        evaluator = None,
+        batch_size = 1,
+        seed = None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        env: UTTTEnvImpl,
+        time_budget_s: Option<f32>,
+        iteration_budget: Option<u64>,
+        rollout_budget: Option<u64>,
+        node_budget: Option<usize>,
+        exploration: f32,
+        draw_value: f32,
+        final_selection: &str,
+        first_play_urgency: Option<f32>,
+        expansion_order: &str,
+        max_nodes: Option<usize>,
+        max_memory_bytes: Option<usize>,
+        threads: usize,
+        parallel: &str,
+        virtual_loss: u32,
+        solver: bool,
+        rave: Option<&str>,
+        rave_equivalence: f32,
+        rave_bias: f32,
+        transpositions: bool,
+        rollout_policy: &str,
+        rollout_epsilon: f64,
+        rollout_temperature: f32,
+        keep_rollout_tables: bool,
+        rollout_depth: Option<u32>,
+        value_function: Option<&PyAny>,
+        dirichlet_alpha: Option<f32>,
+        dirichlet_epsilon: f32,
+        selection: &str,
+        evaluator: Option<&PyAny>,
+        batch_size: usize,
+        seed: Option<u64>,
+    ) -> PyResult<Self> {
+        if !(exploration.is_finite() && exploration >= 0.0) {
+            return Err(PyValueError::new_err("exploration must be a finite non-negative number"));
+        }
+        if !draw_value.is_finite() {
+            return Err(PyValueError::new_err("draw_value must be a finite number"));
+        }
+        if threads == 0 {
+            return Err(PyValueError::new_err("threads must be greater than 0"));
+        }
+        if batch_size == 0 {
+            return Err(PyValueError::new_err("batch_size must be greater than 0"));
+        }
+        if dirichlet_alpha.is_some_and(|alpha| !(alpha.is_finite() && alpha > 0.0)) {
+            return Err(PyValueError::new_err("dirichlet_alpha must be a finite number greater than 0"));
+        }
+        if !(0.0..=1.0).contains(&dirichlet_epsilon) {
+            return Err(PyValueError::new_err("dirichlet_epsilon must be between 0 and 1"));
+        }
+        let budget = Budget::new(time_budget_s, iteration_budget, rollout_budget, node_budget)?;
+        let config = MctsConfig {
+            exploration,
+            draw_value,
+            final_selection: FinalSelection::from_name(final_selection)?,
+            first_play_urgency,
+            expansion_order: ExpansionOrder::from_name(expansion_order)?,
+            max_nodes,
+            max_memory_bytes,
+            virtual_loss,
+            solver,
+            rave: Rave::from_name(rave, rave_equivalence, rave_bias)?,
+            transpositions,
+            rollout_policy: crate::rollout::from_name(rollout_policy, rollout_epsilon, rollout_temperature)?,
+            keep_rollout_tables,
+            rollout_depth,
+            value_function: crate::value::from_py(value_function)?,
+            dirichlet_alpha,
+            dirichlet_epsilon,
+            selection: Selection::from_name(selection)?,
+            evaluator: evaluator::from_py(evaluator)?,
+            batch_size,
+        };
+        let n_trees = match Parallelism::from_name(parallel)? {
+            Parallelism::Root => threads,
+            Parallelism::Tree => 1,
+        };
+        let trees = (0..n_trees)
+            .map(|_| RwLock::new(Tree::new(&env.game, &config)))
+            .collect();
+        let mut rng = crate::rng::from_seed(seed);
+        let workers = (0..threads)
+            .map(|i| Worker::new(i % n_trees, StdRng::from_rng(&mut rng).unwrap()))
+            .collect();
+        let mut mcts = MCTS {
+            trees,
+            workers,
+            background: None,
+            root_state: env.game,
+            budget,
+            config,
+            last_search: SearchInfo::default(),
+            rng,
+        };
+        mcts.apply_noise();
+        Ok(mcts)
+    }
+
+    /// Searches until the budget is exhausted and returns the chosen action.
+    /// The GIL is released for the duration of the search.
+    /// A background search is stopped first and its tree is reused.
+    /// With a `temperature`, the action is sampled from the root visit
+    /// distribution sharpened by it instead of chosen by the final
+    /// selection rule; 0 picks the most visited action.
+    #[pyo3(signature = (temperature = None))]
+    pub fn run(&mut self, py: Python, temperature: Option<f32>) -> PyResult<u8> {
+        if temperature.is_some_and(|temperature| !(temperature >= 0.0 && temperature.is_finite())) {
+            return Err(PyValueError::new_err("temperature must be a non-negative number"));
+        }
+        py.allow_threads(|| {
+            // a failed background search would fail again here
+            self.stop_background();
+            self.search(temperature)
+        })
+    }
+
+    /// Keeps searching in the background, e.g. on the opponent's time,
+    /// until `stop`, `run` or `move_root` is called.
+    pub fn start_pondering(&mut self, py: Python) -> PyResult<()> {
+        self.start_background(py, Budget::unlimited(), true)?;
+        Ok(())
+    }
+
+    /// Starts a search on the budget in the background and returns a handle
+    /// to poll, await or cancel it. The search is cancelled by `stop`, `run`,
+    /// `move_root` and `compact`.
+    pub fn start_search(&mut self, py: Python) -> PyResult<SearchHandle> {
+        let status = self.start_background(py, self.budget.clone(), false)?;
+        Ok(SearchHandle {
+            status,
+            trees: self.trees.clone(),
+            config: self.config.clone(),
+        })
+    }
+
+    /// Stops the background search and returns the work it did, or `None`
+    /// if there was none. Raises the exception that ended it, if any.
+    pub fn stop(&mut self, py: Python) -> PyResult<Option<SearchInfo>> {
+        match py.allow_threads(|| self.stop_background()) {
+            Some(SearchResult { error: Some(err), .. }) => Err(err.clone_ref(py)),
+            result => Ok(result.map(|result| result.info)),
+        }
+    }
+
+    pub fn is_pondering(&self) -> bool {
+        self.background.as_ref().is_some_and(|background| background.ponder && !background.is_finished())
+    }
+
+    /// Moves the root to the child reached by `action` and discards the rest
+    /// of the tree. A background search is stopped first, so the subtree it
+    /// grew for `action` is kept. The tables of an adaptive rollout policy
+    /// are cleared unless `keep_rollout_tables` is set. Returns the number of
+    /// nodes reclaimed. An action that is not valid at the root raises
+    /// `ValueError` and changes nothing.
+    pub fn move_root(&mut self, py: Python, action: u8) -> PyResult<usize> {
+        if action >= 81 {
+            return Err(PyValueError::new_err(format!("action {} is out of range", action)));
+        }
+        let m = action_to_move(action);
+        if !self.root_state.move_valid(m) {
+            return Err(PyValueError::new_err(format!("action {} is not valid at the root", action)));
+        }
+        py.allow_threads(|| self.stop_background());
+        if !self.config.keep_rollout_tables {
+            self.config.rollout_policy.reset();
+        }
+        self.root_state.make_move(m);
+        let reclaimed = self.trees.iter()
+            .map(|tree| tree.write().unwrap().move_root(m, &self.root_state))
+            .sum();
+        self.apply_noise();
+        Ok(reclaimed)
+    }
+
+    /// Drops every node not reachable from the root and renumbers the rest.
+    /// A background search is stopped first. Returns the number of nodes
+    /// reclaimed.
+    pub fn compact(&mut self, py: Python) -> usize {
+        py.allow_threads(|| self.stop_background());
+        self.trees.iter().map(|tree| tree.write().unwrap().compact()).sum()
+    }
+
+    /// Number of nodes in all trees. Every node is reachable from a root.
+    pub fn tree_size(&self) -> usize {
+        self.trees.iter().map(|tree| tree.read().unwrap().size()).sum()
+    }
+
+    /// Approximate number of bytes used by all trees.
+    pub fn memory_usage(&self) -> usize {
+        self.trees.iter().map(|tree| tree.read().unwrap().memory()).sum()
+    }
+
+    pub fn root_value(&self) -> f32 {
+        root_value(&self.trees, self.config.draw_value)
+    }
+
+    /// Root visit distribution as 81 probabilities indexed by action, for
+    /// use as a policy target. Visits are raised to `1 / temperature` before
+    /// normalizing; 0 puts all the mass on the most visited action.
+    #[pyo3(signature = (temperature = 1.0))]
+    pub fn root_policy(&self, temperature: f32) -> PyResult<Vec<f32>> {
+        if !(temperature >= 0.0 && temperature.is_finite()) {
+            return Err(PyValueError::new_err("temperature must be a non-negative number"));
+        }
+        Ok(root_policy(&self.trees, temperature))
+    }
+
+    /// Win, draw and loss probabilities of the player to move at the root,
+    /// `None` before any search.
+    pub fn root_wdl(&self) -> Option<WdlStats> {
+        WdlStats::new(root_wdl(&self.trees).other(), root_visits(&self.trees))
+    }
+
+    /// Changes the reward of a draw. The tree keeps counts of wins, draws
+    /// and losses, so values and the chosen move follow without searching
+    /// again. A background search keeps the old value until it is stopped.
+    pub fn set_draw_value(&mut self, draw_value: f32) -> PyResult<()> {
+        if !draw_value.is_finite() {
+            return Err(PyValueError::new_err("draw_value must be a finite number"));
+        }
+        self.config.draw_value = draw_value;
+        Ok(())
+    }
+
+    /// Result proven by the solver for the player to move at the root:
+    /// "win", "loss" or "draw", or `None` if the root is not proven.
+    pub fn root_proof(&self) -> Option<&'static str> {
+        root_proof(&self.trees).other().name()
+    }
+
+    /// Statistics of every root child, merged across trees, and of the
+    /// last search on the budget.
+    pub fn stats(&mut self, py: Python) -> SearchStats {
+        self.collect_finished(py);
+        stats::stats(&self.trees, &self.config, &self.last_search)
+    }
+
+    /// Most visited line from the root, empty before any search.
+    pub fn principal_variation(&self) -> Vec<u8> {
+        pv::multi_pv(&self.trees, 1, self.config.draw_value).pop().map_or(Vec::new(), |pv| pv.line)
+    }
+
+    /// The `k` most visited root moves, each with its own line and value.
+    pub fn multi_pv(&self, k: usize) -> Vec<PvLine> {
+        pv::multi_pv(&self.trees, k, self.config.draw_value)
+    }
+
+    /// Read-only handle to the root node. With root parallelism `tree`
+    /// picks which thread's tree to look at.
+    #[pyo3(signature = (tree = 0))]
+    pub fn root_node(&self, tree: usize) -> PyResult<TreeNode> {
+        self.tree(tree)?;
+        Ok(TreeNode::root(self.trees.clone(), tree, self.root_state.clone(), self.config.clone()))
+    }
+
+    /// The tree as nested JSON objects with the move, visits, value, UCT
+    /// score, proof and children of every node, down to `max_depth` plies
+    /// and leaving out nodes with fewer than `min_visits` visits. With root
+    /// parallelism `tree` picks which thread's tree to export.
+    #[pyo3(signature = (max_depth = None, min_visits = 1, tree = 0))]
+    pub fn export_json(&self, max_depth: Option<u32>, min_visits: u32, tree: usize) -> PyResult<String> {
+        let filter = export::Filter { max_depth, min_visits };
+        Ok(export::to_json(&self.tree(tree)?.read().unwrap(), &self.config, &filter))
+    }
+
+    /// The tree as a Graphviz digraph, with the same options as
+    /// `export_json`.
+    #[pyo3(signature = (max_depth = None, min_visits = 1, tree = 0))]
+    pub fn export_dot(&self, max_depth: Option<u32>, min_visits: u32, tree: usize) -> PyResult<String> {
+        let filter = export::Filter { max_depth, min_visits };
+        Ok(export::to_dot(&self.tree(tree)?.read().unwrap(), &self.config, &filter))
+    }
+
+    /// Work done by the last search on the budget, summed over all threads.
+    pub fn last_search(&mut self, py: Python) -> SearchInfo {
+        self.collect_finished(py);
+        self.last_search.clone()
+    }
+}
+
+impl MCTS {
+    fn tree(&self, i: usize) -> PyResult<&RwLock<Tree>> {
+        self.trees.get(i).ok_or_else(|| PyValueError::new_err(format!(
+            "tree {} does not exist, there are {} trees", i, self.trees.len())))
+    }
+
+    /// Draws fresh Dirichlet noise over the root children, shared by all
+    /// trees, if noise is enabled.
+    fn apply_noise(&mut self) {
+        let Some(alpha) = self.config.dirichlet_alpha else { return };
+        let gamma = Gamma::new(alpha, 1.0).unwrap();
+        let n = self.trees[0].read().unwrap().root().children.len();
+        let mut noise: Vec<f32> = (0..n).map(|_| gamma.sample(&mut self.rng)).collect();
+        let total: f32 = noise.iter().sum();
+        noise.iter_mut().for_each(|x| *x = if total > 0.0 { *x / total } else { 1.0 / n as f32 });
+        for tree in self.trees.iter() {
+            tree.write().unwrap().noise = noise.clone();
+        }
+    }
+
+    fn search(&mut self, temperature: Option<f32>) -> PyResult<u8> {
+        self.last_search = search::search(
+            &self.trees,
+            &mut self.workers,
+            &self.root_state,
+            &self.config,
+            &self.budget,
+            &Status::default(),
+        )?;
+        let child = match temperature {
+            Some(temperature) => sample_root_child(&self.trees, &self.config, temperature, &mut self.rng),
+            None => best_root_child(&self.trees, &self.config),
+        };
+        Ok(move_to_action(child.unwrap().action))
+    }
+
+    /// Takes the workers back from a background search that has finished.
+    fn collect_finished(&mut self, py: Python) {
+        if self.background.as_ref().is_some_and(Background::is_finished) {
+            py.allow_threads(|| self.stop_background());
+        }
+    }
+
+    /// Hands the workers to a background search. A finished background
+    /// search is collected first, a running one is an error.
+    fn start_background(&mut self, py: Python, budget: Budget, ponder: bool) -> PyResult<Arc<Status>> {
+        self.collect_finished(py);
+        if self.background.is_some() {
+            return Err(PyRuntimeError::new_err("a background search is already running"));
+        }
+        let background = Background::spawn(
+            self.trees.clone(),
+            std::mem::take(&mut self.workers),
+            self.root_state.clone(),
+            self.config.clone(),
+            budget,
+            ponder,
+        );
+        let status = background.status.clone();
+        self.background = Some(background);
+        Ok(status)
+    }
+
+    fn stop_background(&mut self) -> Option<SearchResult> {
+        let background = self.background.take()?;
+        let ponder = background.ponder;
+        let (workers, result) = background.stop();
+        self.workers = workers;
+        if !ponder {
+            self.last_search = result.info.clone();
+        }
+        Some(result)
+    }
+}
+
+impl Drop for MCTS {
+    fn drop(&mut self) {
+        // the background search may be waiting for the GIL to call a
+        // Python value function
+        if self.background.is_some() {
+            Python::with_gil(|py| py.allow_threads(|| self.stop_background()));
+        }
+    }
+}
@@ -0,0 +1,373 @@
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicU32, Ordering};
+use crate::game::{Game, Move};
+use crate::atomic::AtomicF32;
+use super::config::{MctsConfig, Rave};
+
+/// Game-theoretic value of a node, from the point of view of the player
+/// who made the move leading to it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Proof {
+    Unknown,
+    Win,
+    Loss,
+    Draw,
+}
+
+impl Proof {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => Proof::Win,
+            2 => Proof::Loss,
+            3 => Proof::Draw,
+            _ => Proof::Unknown,
+        }
+    }
+
+    fn to_u8(self) -> u8 {
+        match self {
+            Proof::Unknown => 0,
+            Proof::Win => 1,
+            Proof::Loss => 2,
+            Proof::Draw => 3,
+        }
+    }
+
+    /// The same value from the point of view of the other player.
+    pub fn other(self) -> Self {
+        match self {
+            Proof::Win => Proof::Loss,
+            Proof::Loss => Proof::Win,
+            proof => proof,
+        }
+    }
+
+    pub fn name(self) -> Option<&'static str> {
+        match self {
+            Proof::Unknown => None,
+            Proof::Win => Some("win"),
+            Proof::Loss => Some("loss"),
+            Proof::Draw => Some("draw"),
+        }
+    }
+
+    /// Result of a proven node, `None` if it is not proven.
+    pub fn wdl(self) -> Option<Wdl> {
+        match self {
+            Proof::Unknown => None,
+            Proof::Win => Some(Wdl::WIN),
+            Proof::Loss => Some(Wdl::LOSS),
+            Proof::Draw => Some(Wdl::DRAW),
+        }
+    }
+}
+
+/// Wins, draws and losses of the player who moved into a node. A single
+/// result is one of the three, or split between a win and a loss when it
+/// is an estimate of a value function.
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub struct Wdl {
+    pub win: f32,
+    pub draw: f32,
+    pub loss: f32,
+}
+
+impl Wdl {
+    pub const WIN: Self = Self { win: 1.0, draw: 0.0, loss: 0.0 };
+    pub const DRAW: Self = Self { win: 0.0, draw: 1.0, loss: 0.0 };
+    pub const LOSS: Self = Self { win: 0.0, draw: 0.0, loss: 1.0 };
+
+    /// Estimated result with expected reward `value`.
+    pub fn value(value: f32) -> Self {
+        Self { win: value, draw: 0.0, loss: 1.0 - value }
+    }
+
+    /// The same results from the point of view of the other player.
+    pub fn other(self) -> Self {
+        Self { win: self.loss, draw: self.draw, loss: self.win }
+    }
+
+    /// Total reward when a draw is worth `draw_value`.
+    pub fn reward(self, draw_value: f32) -> f32 {
+        self.win + draw_value * self.draw
+    }
+}
+
+impl std::ops::AddAssign for Wdl {
+    fn add_assign(&mut self, other: Self) {
+        self.win += other.win;
+        self.draw += other.draw;
+        self.loss += other.loss;
+    }
+}
+
+/// All-moves-as-first statistics of a move: results of the playouts in
+/// which the player to move played it at any later point.
+pub struct Amaf {
+    visits: AtomicU32,
+    reward: AtomicF32,
+}
+
+impl Amaf {
+    fn new() -> Self {
+        Self {
+            visits: AtomicU32::new(0),
+            reward: AtomicF32::new(0.0),
+        }
+    }
+
+    pub fn visits(&self) -> u32 {
+        self.visits.load(Ordering::Relaxed)
+    }
+
+    pub fn mean(&self) -> f32 {
+        self.reward.load() / self.visits() as f32
+    }
+
+    pub fn update(&self, reward: f32) {
+        self.visits.fetch_add(1, Ordering::Relaxed);
+        self.reward.add(reward);
+    }
+}
+
+/// Node of the search tree. The statistics are atomic so that threads
+/// sharing the tree can update them while holding only a read lock.
+///
+/// When transpositions are tracked a node stands for a position and may be
+/// the child of several nodes, so it knows neither its parent nor the move
+/// leading to it.
+pub struct Node {
+    visits: AtomicU32,
+    /// Wins and draws, the losses are the rest of the visits. The reward is
+    /// only worked out when needed, so the value of a draw can change
+    /// without searching again.
+    wins: AtomicF32,
+    draws: AtomicF32,
+    /// Losses added for iterations that passed through the node and have
+    /// not been backed up yet.
+    virtual_loss: AtomicU32,
+    proof: AtomicU8,
+    /// Hash of the position, 0 unless transpositions are tracked.
+    pub hash: u64,
+    pub children: Vec<(Move, Option<usize>)>,
+    /// AMAF statistics of each child, empty unless RAVE is enabled.
+    pub amaf: Vec<Amaf>,
+    /// Visits of the edge to each child, empty unless transpositions are
+    /// tracked. A child reached from several parents counts all its visits,
+    /// the edge only those made through this node.
+    edge_visits: Vec<AtomicU32>,
+    /// Prior probability of each child, empty unless PUCT is used. Uniform
+    /// until the node has been evaluated.
+    priors: Vec<AtomicF32>,
+    evaluated: AtomicBool,
+}
+
+impl Node {
+    pub fn new(state: &Game, amaf: bool, priors: bool, hash: Option<u64>) -> Self {
+        let proof = match (state.is_over(), state.winner()) {
+            (false, _) => Proof::Unknown,
+            (true, None) => Proof::Draw,
+            (true, Some(winner)) => if winner == state.current_player() { Proof::Loss } else { Proof::Win },
+        };
+        let children: Vec<_> = state.valid_moves().into_iter().map(|action| (action, None)).collect();
+        Self {
+            amaf: if amaf { children.iter().map(|_| Amaf::new()).collect() } else { Vec::new() },
+            edge_visits: if hash.is_some() { children.iter().map(|_| AtomicU32::new(0)).collect() } else { Vec::new() },
+            priors: if priors {
+                children.iter().map(|_| AtomicF32::new(1.0 / children.len() as f32)).collect()
+            } else {
+                Vec::new()
+            },
+            evaluated: AtomicBool::new(false),
+            hash: hash.unwrap_or(0),
+            children,
+            visits: AtomicU32::new(0),
+            wins: AtomicF32::new(0.0),
+            draws: AtomicF32::new(0.0),
+            virtual_loss: AtomicU32::new(0),
+            proof: AtomicU8::new(proof.to_u8()),
+        }
+    }
+
+    /// Approximate number of bytes used by the node and its child lists.
+    pub fn memory(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.children.capacity() * std::mem::size_of::<(Move, Option<usize>)>()
+            + self.amaf.capacity() * std::mem::size_of::<Amaf>()
+            + self.edge_visits.capacity() * std::mem::size_of::<AtomicU32>()
+            + self.priors.capacity() * std::mem::size_of::<AtomicF32>()
+    }
+
+    pub fn fully_expanded(&self) -> bool {
+        self.children.iter().all(|(_, node)| node.is_some())
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    pub fn visits(&self) -> u32 {
+        self.visits.load(Ordering::Relaxed)
+    }
+
+    /// Wins, draws and losses over all visits.
+    pub fn wdl(&self) -> Wdl {
+        let (win, draw) = (self.wins.load(), self.draws.load());
+        Wdl { win, draw, loss: self.visits() as f32 - win - draw }
+    }
+
+    pub fn reward(&self, draw_value: f32) -> f32 {
+        self.wins.load() + draw_value * self.draws.load()
+    }
+
+    pub fn update(&self, result: Wdl) {
+        self.visits.fetch_add(1, Ordering::Relaxed);
+        self.wins.add(result.win);
+        self.draws.add(result.draw);
+    }
+
+    /// Counts a visit of the edge to the `i`th child, if edges are tracked.
+    pub fn visit_edge(&self, i: usize) {
+        if let Some(visits) = self.edge_visits.get(i) {
+            visits.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Prior probability of the `i`th child, `None` without priors.
+    pub fn prior(&self, i: usize) -> Option<f32> {
+        self.priors.get(i).map(AtomicF32::load)
+    }
+
+    /// Whether the priors have been set by an evaluator.
+    pub fn is_evaluated(&self) -> bool {
+        self.evaluated.load(Ordering::Acquire)
+    }
+
+    pub fn set_priors(&self, priors: &[f32]) {
+        for (prior, &p) in self.priors.iter().zip(priors) {
+            prior.store(p);
+        }
+        self.evaluated.store(true, Ordering::Release);
+    }
+
+    pub fn proof(&self) -> Proof {
+        Proof::from_u8(self.proof.load(Ordering::Relaxed))
+    }
+
+    pub fn set_proof(&self, proof: Proof) {
+        self.proof.store(proof.to_u8(), Ordering::Relaxed);
+    }
+
+    pub fn add_virtual_loss(&self, amount: u32) {
+        self.virtual_loss.fetch_add(amount, Ordering::Relaxed);
+    }
+
+    pub fn remove_virtual_loss(&self, amount: u32) {
+        self.virtual_loss.fetch_sub(amount, Ordering::Relaxed);
+    }
+
+    /// Visits including the pending ones counted as virtual losses.
+    fn effective_visits(&self) -> u32 {
+        self.visits() + self.virtual_loss.load(Ordering::Relaxed)
+    }
+
+    /// UCT value of the node. With RAVE, the mean is blended with the AMAF
+    /// mean of the move leading to the node. With transpositions, the
+    /// exploration term uses the visits of the edge leading to the node.
+    pub fn uct_value(
+        &self,
+        parent_visits: u32,
+        c: f32,
+        draw_value: f32,
+        rave: Option<(Rave, &Amaf)>,
+        edge_visits: Option<u32>,
+    ) -> f32 {
+        let visits = self.effective_visits();
+        assert!(parent_visits > 0, "Parent visits must be greater than 0");
+        assert!(visits > 0, "Visits must be greater than 0");
+        let mut mean = self.reward(draw_value) / visits as f32;
+        if let Some((rave, amaf)) = rave {
+            if amaf.visits() > 0 {
+                let beta = rave.beta(visits, amaf.visits());
+                mean = (1.0 - beta) * mean + beta * amaf.mean();
+            }
+        }
+        let explored = edge_visits.map_or(visits, |edge_visits| edge_visits.max(1));
+        mean + c * ((parent_visits as f32).ln() / explored as f32).sqrt()
+    }
+
+    /// UCT value of the `i`th child, `child`, as seen from this node. A child
+    /// another thread has added but not backed up yet, which only happens
+    /// without virtual loss, counts as unvisited.
+    pub fn child_uct_value(&self, i: usize, child: &Node, config: &MctsConfig) -> f32 {
+        self.weighted_uct_value(i, child, config, 1.0)
+    }
+
+    /// `child_uct_value` with the exploration term scaled by `weight`.
+    fn weighted_uct_value(&self, i: usize, child: &Node, config: &MctsConfig, weight: f32) -> f32 {
+        if child.effective_visits() == 0 {
+            return config.first_play_urgency.unwrap_or(f32::INFINITY);
+        }
+        let rave = config.rave.map(|rave| (rave, &self.amaf[i]));
+        let edge_visits = self.edge_visits.get(i).map(|visits| visits.load(Ordering::Relaxed));
+        child.uct_value(self.effective_visits().max(1), weight * config.exploration, config.draw_value, rave, edge_visits)
+    }
+
+    /// PUCT value of the `i`th child, `child` if expanded, as seen from this
+    /// node. `prior` replaces the stored prior. An unvisited child is worth
+    /// `first_play_urgency`, or else the value of this node to the player
+    /// choosing.
+    pub fn puct_value(&self, i: usize, child: Option<&Node>, prior: f32, config: &MctsConfig) -> f32 {
+        let visits = child.map_or(0, Node::effective_visits);
+        let mean = if visits > 0 {
+            child.unwrap().reward(config.draw_value) / visits as f32
+        } else {
+            config.first_play_urgency.unwrap_or_else(|| match self.visits() {
+                0 => 0.5,
+                n => 1.0 - self.reward(config.draw_value) / n as f32,
+            })
+        };
+        let explored = self.edge_visits.get(i).map_or(visits, |visits| visits.load(Ordering::Relaxed));
+        mean + config.exploration * prior * (self.effective_visits() as f32).sqrt() / (1 + explored) as f32
+    }
+
+    /// Index of the child, expanded or not, with the highest PUCT value.
+    /// With the solver enabled, children proven to lose are never chosen.
+    /// `noise`, empty or one value per child summing to 1, is mixed into
+    /// the priors with weight `epsilon`.
+    pub fn puct_child(&self, tree: &[Node], config: &MctsConfig, noise: &[f32]) -> Option<usize> {
+        let epsilon = config.dirichlet_epsilon;
+        self.children
+            .iter()
+            .enumerate()
+            .map(|(i, (_, node))| (i, node.map(|node| &tree[node])))
+            .filter(|&(_, child)| !config.solver || child.is_none_or(|child| child.proof() != Proof::Loss))
+            .map(|(i, child)| {
+                let prior = self.priors[i].load();
+                let prior = noise.get(i).map_or(prior, |noise| (1.0 - epsilon) * prior + epsilon * noise);
+                (i, self.puct_value(i, child, prior, config))
+            })
+            .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+            .map(|(i, _)| i)
+    }
+
+    /// Index of the expanded child with the highest UCT value, together with
+    /// that value. With the solver enabled, children proven to lose are never
+    /// chosen. `noise`, empty or one value per child summing to 1, scales
+    /// the exploration term of each child by `(1 - epsilon) + epsilon * n *
+    /// noise`, `n` being the number of children, as if mixed into a uniform
+    /// prior.
+    pub fn uct_child(&self, tree: &[Node], config: &MctsConfig, noise: &[f32]) -> Option<(usize, f32)> {
+        let epsilon = config.dirichlet_epsilon;
+        let n = self.children.len() as f32;
+        self.children
+            .iter()
+            .enumerate()
+            .filter_map(|(i, (_, node))| node.map(|node| (i, node)))
+            .filter(|&(_, child)| !config.solver || tree[child].proof() != Proof::Loss)
+            .map(|(i, child)| {
+                let weight = noise.get(i).map_or(1.0, |noise| 1.0 + epsilon * (n * noise - 1.0));
+                (i, self.weighted_uct_value(i, &tree[child], config, weight))
+            })
+            .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+    }
+}
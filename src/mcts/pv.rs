@@ -0,0 +1,77 @@
+use std::sync::RwLock;
+use pyo3::prelude::*;
+use crate::env::move_to_action;
+use crate::game::Move;
+use super::root::root_children;
+use super::tree::Tree;
+
+/// A candidate root move with the line the search expects to follow it.
+#[pyclass]
+#[derive(Clone, Debug)]
+pub struct PvLine {
+    #[pyo3(get)]
+    pub action: u8,
+    #[pyo3(get)]
+    pub visits: u32,
+    /// Mean reward of the player to move at the root.
+    #[pyo3(get)]
+    pub value: f32,
+    /// Actions of the line, starting with `action`.
+    #[pyo3(get)]
+    pub line: Vec<u8>,
+}
+
+#[pymethods]
+impl PvLine {
+    fn __repr__(&self) -> String {
+        format!("PvLine(action={}, visits={}, value={:.3}, line={:?})",
+            self.action, self.visits, self.value, self.line)
+    }
+}
+
+/// The `k` most visited root moves, merged across trees, each followed by
+/// the most visited line below it. The line is taken from the tree that
+/// visited the move the most.
+pub fn multi_pv(trees: &[RwLock<Tree>], k: usize, draw_value: f32) -> Vec<PvLine> {
+    let mut children = root_children(trees);
+    children.sort_by_key(|child| std::cmp::Reverse(child.visits));
+    children.into_iter()
+        .take(k)
+        .map(|child| {
+            let line = trees.iter()
+                .map(|tree| tree.read().unwrap())
+                .max_by_key(|tree| child_node(tree, tree.root, child.action)
+                    .map_or(0, |node| tree.nodes[node].visits()))
+                .map(|tree| line(&tree, child.action))
+                .unwrap();
+            PvLine {
+                action: move_to_action(child.action),
+                visits: child.visits,
+                value: child.mean(draw_value),
+                line,
+            }
+        })
+        .collect()
+}
+
+fn child_node(tree: &Tree, node: usize, m: Move) -> Option<usize> {
+    tree.nodes[node].children.iter().find(|&&(a, _)| a == m).and_then(|&(_, child)| child)
+}
+
+/// `first` followed by the most visited child at every node below it.
+fn line(tree: &Tree, first: Move) -> Vec<u8> {
+    let mut line = vec![move_to_action(first)];
+    let mut node = child_node(tree, tree.root, first);
+    while let Some(n) = node {
+        let best = tree.nodes[n].children
+            .iter()
+            .filter_map(|&(m, child)| child.map(|child| (m, child)))
+            .filter(|&(_, child)| tree.nodes[child].visits() > 0)
+            .max_by_key(|&(_, child)| tree.nodes[child].visits());
+        node = best.map(|(m, child)| {
+            line.push(move_to_action(m));
+            child
+        });
+    }
+    line
+}
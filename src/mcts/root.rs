@@ -0,0 +1,177 @@
+use std::sync::RwLock;
+use rand::Rng;
+use rand::rngs::StdRng;
+use crate::env::move_to_action;
+use crate::game::Move;
+use super::config::{MctsConfig, FinalSelection};
+use super::node::{Proof, Wdl};
+use super::tree::Tree;
+
+/// Statistics of a root child summed over all trees.
+pub struct RootChild {
+    pub action: Move,
+    pub visits: u32,
+    pub wdl: Wdl,
+    /// Proof found in any of the trees.
+    pub proof: Proof,
+}
+
+impl RootChild {
+    pub fn mean(&self, draw_value: f32) -> f32 {
+        self.wdl.reward(draw_value) / self.visits as f32
+    }
+
+    pub fn uct_value(&self, parent_visits: u32, c: f32, draw_value: f32) -> f32 {
+        self.mean(draw_value) + c * ((parent_visits as f32).ln() / self.visits as f32).sqrt()
+    }
+
+    fn lcb_value(&self, parent_visits: u32, c: f32, draw_value: f32) -> f32 {
+        self.mean(draw_value) - c * ((parent_visits as f32).ln() / self.visits as f32).sqrt()
+    }
+}
+
+/// Visited root children with their statistics merged across trees.
+pub fn root_children(trees: &[RwLock<Tree>]) -> Vec<RootChild> {
+    let mut children = all_root_children(trees);
+    children.retain(|child| child.visits > 0);
+    children
+}
+
+/// Every root child, visited or not, with its statistics merged across trees.
+pub fn all_root_children(trees: &[RwLock<Tree>]) -> Vec<RootChild> {
+    let mut children: Vec<RootChild> = trees[0].read().unwrap().root().children
+        .iter()
+        .map(|&(action, _)| RootChild { action, visits: 0, wdl: Wdl::default(), proof: Proof::Unknown })
+        .collect();
+    for tree in trees {
+        let tree = tree.read().unwrap();
+        for (child, (_, node)) in children.iter_mut().zip(&tree.root().children) {
+            if let Some(node) = node {
+                child.visits += tree.nodes[*node].visits();
+                child.wdl += tree.nodes[*node].wdl();
+                if child.proof == Proof::Unknown {
+                    child.proof = tree.nodes[*node].proof();
+                }
+            }
+        }
+    }
+    children
+}
+
+pub fn root_visits(trees: &[RwLock<Tree>]) -> u32 {
+    trees.iter().map(|tree| tree.read().unwrap().root().visits()).sum()
+}
+
+pub fn root_value(trees: &[RwLock<Tree>], draw_value: f32) -> f32 {
+    root_wdl(trees).reward(draw_value) / root_visits(trees) as f32
+}
+
+/// Results at the root summed over all trees, from the point of view of
+/// the player who moved into it.
+pub fn root_wdl(trees: &[RwLock<Tree>]) -> Wdl {
+    let mut wdl = Wdl::default();
+    for tree in trees {
+        wdl += tree.read().unwrap().root().wdl();
+    }
+    wdl
+}
+
+/// Proof of the root found in any of the trees, from the point of view of
+/// the player who moved into it.
+pub fn root_proof(trees: &[RwLock<Tree>]) -> Proof {
+    trees.iter()
+        .map(|tree| tree.read().unwrap().root().proof())
+        .find(|&proof| proof != Proof::Unknown)
+        .unwrap_or(Proof::Unknown)
+}
+
+/// Visited root children the move may be chosen from. With the solver
+/// enabled, a proven win is the only candidate and proven losses are
+/// candidates only if nothing else is left.
+fn candidates(trees: &[RwLock<Tree>], config: &MctsConfig) -> Vec<RootChild> {
+    let mut children = root_children(trees);
+    if config.solver {
+        if let Some(i) = children.iter().position(|child| child.proof == Proof::Win) {
+            return vec![children.swap_remove(i)];
+        }
+        if children.iter().any(|child| child.proof != Proof::Loss) {
+            children.retain(|child| child.proof != Proof::Loss);
+        }
+    }
+    children
+}
+
+/// Root child sampled with probability proportional to its visits raised to
+/// `1 / temperature`, among the same candidates as the final selection.
+/// A temperature of 0 picks the most visited child.
+pub fn sample_root_child(
+    trees: &[RwLock<Tree>],
+    config: &MctsConfig,
+    temperature: f32,
+    rng: &mut StdRng,
+) -> Option<RootChild> {
+    let children = candidates(trees, config);
+    if temperature == 0.0 {
+        return children.into_iter().max_by_key(|child| child.visits);
+    }
+    let weights = visit_weights(&children, temperature);
+    let mut target = rng.gen::<f32>() * weights.iter().sum::<f32>();
+    let last = children.len().checked_sub(1)?;
+    let i = weights.iter()
+        .position(|&weight| {
+            target -= weight;
+            target < 0.0
+        })
+        .unwrap_or(last);
+    children.into_iter().nth(i)
+}
+
+/// Visits of `children` raised to `1 / temperature`, scaled so that the
+/// largest is 1 to stay finite at low temperatures.
+fn visit_weights(children: &[RootChild], temperature: f32) -> Vec<f32> {
+    let max = children.iter().map(|child| child.visits).max().unwrap_or(1) as f32;
+    children.iter().map(|child| (child.visits as f32 / max).powf(1.0 / temperature)).collect()
+}
+
+/// Root visit distribution as 81 probabilities indexed by action, sharpened
+/// or flattened by `temperature` like `sample_root_child`. All zeros before
+/// any search.
+pub fn root_policy(trees: &[RwLock<Tree>], temperature: f32) -> Vec<f32> {
+    let children = root_children(trees);
+    let mut policy = vec![0.0; 81];
+    if temperature == 0.0 {
+        if let Some(best) = children.iter().max_by_key(|child| child.visits) {
+            policy[move_to_action(best.action) as usize] = 1.0;
+        }
+        return policy;
+    }
+    let weights = visit_weights(&children, temperature);
+    let total: f32 = weights.iter().sum();
+    for (child, weight) in children.iter().zip(weights) {
+        policy[move_to_action(child.action) as usize] = weight / total;
+    }
+    policy
+}
+
+/// Root child chosen by the final selection rule. With the solver enabled,
+/// a proven win is always chosen and proven losses only if nothing else is
+/// left.
+pub fn best_root_child(trees: &[RwLock<Tree>], config: &MctsConfig) -> Option<RootChild> {
+    let children = candidates(trees, config).into_iter();
+    let draw_value = config.draw_value;
+    match config.final_selection {
+        FinalSelection::Robust => children.max_by_key(|child| child.visits),
+        FinalSelection::Max => children.max_by(|a, b|
+            a.mean(draw_value)
+                .partial_cmp(&b.mean(draw_value))
+                .unwrap()),
+        FinalSelection::Secure => {
+            let visits = root_visits(trees);
+            let c = config.exploration;
+            children.max_by(|a, b|
+                a.lcb_value(visits, c, draw_value)
+                    .partial_cmp(&b.lcb_value(visits, c, draw_value))
+                    .unwrap())
+        }
+    }
+}
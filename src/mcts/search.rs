@@ -0,0 +1,661 @@
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, RwLock};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use pyo3::prelude::*;
+use rand::rngs::StdRng;
+use rand::seq::IteratorRandom;
+use crate::env::move_to_action;
+use crate::game::{Game, Player, Move};
+use crate::budget::{Budget, SearchInfo};
+use super::config::{MctsConfig, ExpansionOrder, Selection};
+use super::evaluator::simulate;
+use super::node::{Node, Proof, Wdl};
+use super::root::{best_root_child, root_proof};
+use super::tree::Tree;
+
+/// Outcome of a finished search.
+#[derive(Clone)]
+pub struct SearchResult {
+    pub info: SearchInfo,
+    /// Root child chosen by the final selection rule, if any was visited.
+    pub best: Option<Move>,
+    /// Exception raised from Python that ended the search.
+    pub error: Option<Arc<PyErr>>,
+}
+
+/// State of a search shared between its workers and anyone watching it.
+pub struct Status {
+    start: Instant,
+    stop: AtomicBool,
+    iterations: AtomicU64,
+    result: Mutex<Option<SearchResult>>,
+    finished: Condvar,
+}
+
+impl Default for Status {
+    fn default() -> Self {
+        Self {
+            start: Instant::now(),
+            stop: AtomicBool::new(false),
+            iterations: AtomicU64::new(0),
+            result: Mutex::new(None),
+            finished: Condvar::new(),
+        }
+    }
+}
+
+impl Status {
+    /// Asks the workers to stop after their current iteration.
+    pub fn cancel(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
+
+    /// Iterations completed so far by all workers.
+    pub fn iterations(&self) -> u64 {
+        self.iterations.load(Ordering::Relaxed)
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// The result once the search has finished. The search cannot finish
+    /// while the guard is held.
+    pub fn result(&self) -> MutexGuard<'_, Option<SearchResult>> {
+        self.result.lock().unwrap()
+    }
+
+    fn finish(&self, result: SearchResult) {
+        *self.result() = Some(result);
+        self.finished.notify_all();
+    }
+
+    /// Blocks until the search has finished or the timeout has passed.
+    pub fn wait(&self, timeout: Option<Duration>) -> Option<SearchResult> {
+        let result = self.result();
+        let result = match timeout {
+            Some(timeout) => self.finished
+                .wait_timeout_while(result, timeout, |result| result.is_none())
+                .unwrap().0,
+            None => self.finished
+                .wait_while(result, |result| result.is_none())
+                .unwrap(),
+        };
+        result.clone()
+    }
+}
+
+/// A search thread and the tree it grows. Several workers may share a tree.
+pub struct Worker {
+    pub tree: usize,
+    pub rng: StdRng,
+    /// Nodes of the last selected path, starting at the root.
+    path: Vec<usize>,
+    /// Index of the child taken from each node of `path` but the last.
+    edges: Vec<usize>,
+    /// Moves from the root to the leaf of the last iteration.
+    line: Vec<Move>,
+    /// Moves of the last rollout.
+    playout: Vec<Move>,
+}
+
+impl Worker {
+    pub fn new(tree: usize, rng: StdRng) -> Self {
+        Self {
+            tree,
+            rng,
+            path: Vec::new(),
+            edges: Vec::new(),
+            line: Vec::new(),
+            playout: Vec::new(),
+        }
+    }
+
+    /// Searches `tree` from `state`, the position at its root, until the
+    /// budget is exhausted or the search is cancelled. An error from Python
+    /// cancels the search for the other workers too.
+    fn search(
+        &mut self,
+        tree: &RwLock<Tree>,
+        state: &Game,
+        config: &MctsConfig,
+        budget: &Budget,
+        status: &Status,
+    ) -> PyResult<SearchInfo> {
+        let mut info = SearchInfo::default();
+        // a node budget cannot be used up once the tree is full
+        let mut filled = false;
+        while !filled && !budget.exhausted(status.start, &info) && !status.is_cancelled() && !solved(tree, config) {
+            let outcomes = match config.selection {
+                Selection::Uct => self.iter(tree, state, config).map(|outcome| vec![outcome]),
+                Selection::Puct => {
+                    let size = budget.remaining(&info)
+                        .map_or(config.batch_size, |remaining| config.batch_size.min(remaining as usize));
+                    self.iter_batch(tree, state, config, size)
+                }
+            }.inspect_err(|_| status.cancel())?;
+            for outcome in outcomes {
+                if outcome.expanded {
+                    info.nodes += 1;
+                }
+                if outcome.transposed {
+                    info.transpositions += 1;
+                }
+                if outcome.dead_end {
+                    info.dead_ends += 1;
+                }
+                if outcome.full && budget.nodes.is_some() {
+                    filled = true;
+                }
+                if outcome.rolled_out {
+                    info.rollouts += 1;
+                }
+                info.max_depth = info.max_depth.max(outcome.depth);
+                info.depth_sum += outcome.depth as u64;
+                info.iterations += 1;
+                status.iterations.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        Ok(info)
+    }
+
+    /// Runs one search iteration.
+    ///
+    /// Selection and backpropagation only need a read lock on the tree, the
+    /// write lock is held just long enough to add the new node. Nodes on the
+    /// selected path carry a virtual loss until the result is backed up so
+    /// that other threads sharing the tree pick different paths.
+    ///
+    /// With the solver enabled, selection stops at proven nodes, whose value
+    /// is backed up without a rollout, and new proofs are propagated towards
+    /// the root.
+    ///
+    /// The result is backed up along the selected path only, so a node
+    /// shared by several parents through transpositions gathers the
+    /// statistics of all of them while every parent explores it by the
+    /// visits of its own edge.
+    ///
+    /// If the value function fails, the virtual loss is taken back and
+    /// nothing is backed up.
+    fn iter(&mut self, lock: &RwLock<Tree>, root_state: &Game, config: &MctsConfig) -> PyResult<Outcome> {
+        let Selected { state, leaf, expanded, transposed, full, dead_end } = self.select(lock, root_state, config);
+
+        // simulation, skipped for proven nodes
+        let proven = {
+            let tree = lock.read().unwrap();
+            let proof = tree.nodes[leaf].proof();
+            if config.solver { proof.wdl() } else { None }
+        };
+        let rolled_out = proven.is_none();
+        let to_move = state.current_player();
+        self.playout.clear();
+        let result = match proven {
+            Some(result) => result,
+            None => self.simulate(state, root_state, config)
+                .inspect_err(|_| remove_virtual_loss(lock, &self.path, config))?,
+        };
+
+        self.backup(lock, leaf, to_move, result, !rolled_out, config);
+        let depth = self.path.len() as u32 - 1;
+        Ok(Outcome { expanded, transposed, full, dead_end, rolled_out, depth })
+    }
+
+    /// Runs up to `size` PUCT iterations whose leaves are scored by one call
+    /// of the evaluator. The virtual losses on the paths already
+    /// selected steer the later selections of the batch elsewhere; once a
+    /// leaf waiting for evaluation is selected again, the batch is closed.
+    /// If the evaluation fails, the virtual losses of the batch are taken
+    /// back and nothing is backed up.
+    fn iter_batch(
+        &mut self,
+        lock: &RwLock<Tree>,
+        root_state: &Game,
+        config: &MctsConfig,
+        size: usize,
+    ) -> PyResult<Vec<Outcome>> {
+        let mut batch = Vec::with_capacity(size);
+        for _ in 0..size {
+            let selected = self.select(lock, root_state, config);
+            // the result of a finished game is known even without the solver
+            let known = {
+                let tree = lock.read().unwrap();
+                let node = &tree.nodes[selected.leaf];
+                if config.solver || node.is_terminal() { node.proof().wdl() } else { None }
+            };
+            if known.is_none() && batch.iter().any(|pending: &Pending| pending.known.is_none() && pending.selected.leaf == selected.leaf) {
+                remove_virtual_loss(lock, &self.path, config);
+                break;
+            }
+            batch.push(Pending { selected, known, path: self.path.clone(), edges: self.edges.clone() });
+        }
+
+        let states: Vec<Game> = batch.iter()
+            .filter(|pending| pending.known.is_none())
+            .map(|pending| pending.selected.state.clone())
+            .collect();
+        let mut evaluations = match config.evaluator.evaluate_batch(&states, config, &mut self.rng) {
+            Ok(evaluations) => evaluations.into_iter(),
+            Err(err) => {
+                for pending in &batch {
+                    remove_virtual_loss(lock, &pending.path, config);
+                }
+                return Err(err);
+            }
+        };
+
+        self.playout.clear();
+        Ok(batch.into_iter()
+            .map(|Pending { selected: Selected { state, leaf, expanded, transposed, full, dead_end }, known, path, edges }| {
+                let result = known.unwrap_or_else(|| {
+                    let (priors, result) = evaluations.next().unwrap();
+                    let tree = lock.read().unwrap();
+                    if !tree.nodes[leaf].is_evaluated() {
+                        tree.nodes[leaf].set_priors(&priors);
+                    }
+                    result.other()
+                });
+                self.path = path;
+                self.edges = edges;
+                self.backup(lock, leaf, state.current_player(), result, config.solver && known.is_some(), config);
+                let depth = self.path.len() as u32 - 1;
+                Outcome { expanded, transposed, full, dead_end, rolled_out: known.is_none(), depth }
+            })
+            .collect())
+    }
+
+    /// Selects a path from the root and adds a node at its end, leaving the
+    /// path in `path`, `edges` and `line` with a virtual loss on every node.
+    fn select(&mut self, lock: &RwLock<Tree>, root_state: &Game, config: &MctsConfig) -> Selected {
+        let mut state = root_state.clone();
+        self.path.clear();
+        self.edges.clear();
+        self.line.clear();
+
+        // selection; with PUCT it stops at an unevaluated node or picks the
+        // unexpanded child to add
+        let mut pending = None;
+        let mut leaf = {
+            let tree = lock.read().unwrap();
+            let mut leaf = tree.root;
+            while !tree.nodes[leaf].is_terminal() && !is_proven(&tree.nodes[leaf], config) {
+                let node = &tree.nodes[leaf];
+                let noise = if leaf == tree.root { &tree.noise[..] } else { &[] };
+                let edge = match config.selection {
+                    Selection::Puct if !node.is_evaluated() => break,
+                    Selection::Puct => match node.puct_child(&tree.nodes, config, noise) {
+                        Some(edge) if node.children[edge].1.is_none() => {
+                            pending = Some(edge);
+                            break;
+                        }
+                        Some(edge) => edge,
+                        None => break,
+                    },
+                    Selection::Uct => {
+                        let best = node.uct_child(&tree.nodes, config, noise);
+                        match (node.fully_expanded(), config.first_play_urgency, best) {
+                            (true, _, Some((edge, _))) => edge,
+                            (false, Some(fpu), Some((edge, value))) if value >= fpu => edge,
+                            _ => break,
+                        }
+                    }
+                };
+                let (action, child) = node.children[edge];
+                self.path.push(leaf);
+                self.edges.push(edge);
+                self.line.push(action);
+                state.make_move(action);
+                leaf = child.unwrap();
+            }
+            self.path.push(leaf);
+            for &n in &self.path {
+                tree.nodes[n].add_virtual_loss(config.virtual_loss);
+            }
+            leaf
+        };
+
+        // expansion, stopped once the tree is full except at the root so
+        // that there is always a move to choose from
+        let mut expanded = false;
+        let mut transposed = false;
+        let full;
+        let dead_end;
+        {
+            let mut tree = lock.write().unwrap();
+            let expandable = !tree.nodes[leaf].is_terminal() && !is_proven(&tree.nodes[leaf], config);
+            full = expandable && leaf != tree.root && tree.is_full(config);
+            dead_end = !expandable;
+            if expandable && !full {
+                let mut unexpanded = tree.nodes[leaf].children
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (_, child))| child.is_none())
+                    .map(|(edge, _)| edge);
+                // another thread may have expanded the last child in the meantime
+                let choice = match config.expansion_order {
+                    // PUCT chose the child during selection
+                    _ if config.selection == Selection::Puct =>
+                        pending.filter(|&edge| tree.nodes[leaf].children[edge].1.is_none()),
+                    // the root noise decides the order at the root
+                    _ if leaf == tree.root && !tree.noise.is_empty() =>
+                        unexpanded.max_by(|&a, &b| tree.noise[a].partial_cmp(&tree.noise[b]).unwrap()),
+                    ExpansionOrder::Random => unexpanded.choose(&mut self.rng),
+                    ExpansionOrder::Ordered => unexpanded.next(),
+                };
+                if let Some(edge) = choice {
+                    let action = tree.nodes[leaf].children[edge].0;
+                    self.line.push(action);
+                    state.make_move(action);
+                    let (child, found) = tree.insert(&state);
+                    tree.nodes[leaf].children[edge].1 = Some(child);
+                    tree.nodes[child].add_virtual_loss(config.virtual_loss);
+                    self.edges.push(edge);
+                    self.path.push(child);
+                    leaf = child;
+                    expanded = !found;
+                    transposed = found;
+                }
+            }
+        }
+
+        Selected { state, leaf, expanded, transposed, full, dead_end }
+    }
+
+    /// Backs `result`, for the player who moved into `leaf`, up the path
+    /// from `leaf`, where `to_move` is to play, and removes the virtual
+    /// losses. With `proving`, the leaf is proven and the proof is
+    /// propagated as far as it goes.
+    fn backup(
+        &mut self,
+        lock: &RwLock<Tree>,
+        leaf: usize,
+        mut to_move: Player,
+        mut result: Wdl,
+        mut proving: bool,
+        config: &MctsConfig,
+    ) {
+        let tree = lock.read().unwrap();
+        let mut played = played_by(&self.playout, to_move);
+        for (depth, &n) in self.path.iter().enumerate().rev() {
+            if proving && n != leaf {
+                proving = prove(&tree, n);
+            }
+            let node = &tree.nodes[n];
+            node.update(result);
+            node.remove_virtual_loss(config.virtual_loss);
+            if let Some(&edge) = self.edges.get(depth) {
+                node.visit_edge(edge);
+            }
+            if tree.amaf {
+                update_amaf(node, &played, to_move, result.other().reward(config.draw_value));
+                if depth > 0 {
+                    let action = tree.nodes[self.path[depth - 1]].children[self.edges[depth - 1]].0;
+                    played[move_to_action(action) as usize] = Some(to_move.other());
+                }
+            }
+            result = result.other();
+            to_move = to_move.other();
+        }
+    }
+
+    /// Result of a rollout from `state`, the position at the leaf, for the
+    /// player who moved into it.
+    fn simulate(&mut self, state: Game, root_state: &Game, config: &MctsConfig) -> PyResult<Wdl> {
+        let first = root_state.current_player();
+        Ok(simulate(state, &mut self.line, &mut self.playout, first, config, &mut self.rng)?.other())
+    }
+}
+
+/// Leaf reached by the selection and expansion of an iteration.
+struct Selected {
+    /// Position at the leaf.
+    state: Game,
+    leaf: usize,
+    /// A node was added to the tree.
+    expanded: bool,
+    /// A new edge led to a node already in the tree.
+    transposed: bool,
+    /// The leaf could not be expanded because the tree is full.
+    full: bool,
+    /// The leaf is a finished game or a proven position, so there was
+    /// nothing to add.
+    dead_end: bool,
+}
+
+/// Leaf of a batch waiting for its result, with the path leading to it.
+struct Pending {
+    selected: Selected,
+    /// Result of a proven leaf, which is not evaluated.
+    known: Option<Wdl>,
+    path: Vec<usize>,
+    edges: Vec<usize>,
+}
+
+/// What a search iteration did.
+struct Outcome {
+    /// A node was added to the tree.
+    expanded: bool,
+    /// A new edge led to a node already in the tree.
+    transposed: bool,
+    /// The leaf could not be expanded because the tree is full.
+    full: bool,
+    /// The leaf is a finished game or a proven position.
+    dead_end: bool,
+    /// The value of the leaf came from a rollout rather than a proof.
+    rolled_out: bool,
+    /// Depth of the leaf below the root.
+    depth: u32,
+}
+
+/// Player who made each action in `playout`, which starts with `to_move`.
+/// Every action is played at most once in a game.
+fn played_by(playout: &[Move], to_move: Player) -> [Option<Player>; 81] {
+    let mut played = [None; 81];
+    let mut player = to_move;
+    for &m in playout {
+        played[move_to_action(m) as usize] = Some(player);
+        player = player.other();
+    }
+    played
+}
+
+/// Credits `reward` to the AMAF statistics of every child of `node` whose
+/// move `to_move` played later in the iteration.
+fn update_amaf(node: &Node, played: &[Option<Player>; 81], to_move: Player, reward: f32) {
+    for ((m, _), amaf) in node.children.iter().zip(&node.amaf) {
+        if played[move_to_action(*m) as usize] == Some(to_move) {
+            amaf.update(reward);
+        }
+    }
+}
+
+/// Takes back the virtual loss of an iteration along `path` when its result
+/// will not be backed up.
+fn remove_virtual_loss(lock: &RwLock<Tree>, path: &[usize], config: &MctsConfig) {
+    let tree = lock.read().unwrap();
+    for &n in path {
+        tree.nodes[n].remove_virtual_loss(config.virtual_loss);
+    }
+}
+
+fn is_proven(node: &Node, config: &MctsConfig) -> bool {
+    config.solver && node.proof() != Proof::Unknown
+}
+
+/// Whether the solver has proven the root, so searching further is pointless.
+fn solved(tree: &RwLock<Tree>, config: &MctsConfig) -> bool {
+    let tree = tree.read().unwrap();
+    is_proven(tree.root(), config)
+}
+
+/// Proves `n` from its children if possible. The player to move at `n`
+/// wins if any child is a proven win for them, and otherwise gets the best
+/// value among the children once they are all expanded and proven.
+/// Returns whether `n` was newly proven.
+fn prove(tree: &Tree, n: usize) -> bool {
+    let node = &tree.nodes[n];
+    if node.proof() != Proof::Unknown {
+        return false;
+    }
+    let mut all_proven = node.fully_expanded();
+    let mut draw = false;
+    for child in node.children.iter().filter_map(|(_, child)| *child) {
+        match tree.nodes[child].proof() {
+            Proof::Win => {
+                node.set_proof(Proof::Loss);
+                return true;
+            }
+            Proof::Draw => draw = true,
+            Proof::Loss => {}
+            Proof::Unknown => all_proven = false,
+        }
+    }
+    if all_proven {
+        node.set_proof(if draw { Proof::Draw } else { Proof::Win });
+    }
+    all_proven
+}
+
+/// Runs every worker on its tree in a thread of its own until the budget is
+/// exhausted or the search is cancelled. Counted budgets are divided between the
+/// workers and tree size limits between the trees. Fails with the first
+/// error from Python, which stops all workers.
+pub fn search(
+    trees: &[RwLock<Tree>],
+    workers: &mut [Worker],
+    state: &Game,
+    config: &MctsConfig,
+    budget: &Budget,
+    status: &Status,
+) -> PyResult<SearchInfo> {
+    let n = workers.len();
+    let config = &config.split(trees.len());
+    let results: Vec<_> = thread::scope(|s| {
+        let workers: Vec<_> = workers
+            .iter_mut()
+            .enumerate()
+            .map(|(i, worker)| {
+                let budget = budget.split(i, n);
+                s.spawn(move || worker.search(&trees[worker.tree], state, config, &budget, status))
+            })
+            .collect();
+        workers.into_iter()
+            .map(|worker| worker.join().unwrap())
+            .collect()
+    });
+    let mut info = SearchInfo::default();
+    for result in results {
+        info += result?;
+    }
+    info.elapsed_s = status.elapsed().as_secs_f32();
+    info.proof = root_proof(trees).other().name();
+    Ok(info)
+}
+
+/// Search running in a background thread. The workers are handed back
+/// once it finishes.
+pub struct Background {
+    pub status: Arc<Status>,
+    /// Whether the search runs until stopped rather than on a budget.
+    pub ponder: bool,
+    handle: JoinHandle<Vec<Worker>>,
+}
+
+impl Background {
+    pub fn spawn(
+        trees: Arc<[RwLock<Tree>]>,
+        mut workers: Vec<Worker>,
+        state: Game,
+        config: MctsConfig,
+        budget: Budget,
+        ponder: bool,
+    ) -> Self {
+        let status = Arc::new(Status::default());
+        let handle = {
+            let status = status.clone();
+            thread::spawn(move || {
+                // the search always finishes, so that waiting for it cannot
+                // hang, and hands the workers back, even after an error
+                let (info, error) = match search(&trees, &mut workers, &state, &config, &budget, &status) {
+                    Ok(info) => (info, None),
+                    Err(err) => {
+                        let info = SearchInfo { elapsed_s: status.elapsed().as_secs_f32(), ..SearchInfo::default() };
+                        (info, Some(Arc::new(err)))
+                    }
+                };
+                let best = best_root_child(&trees, &config).map(|child| child.action);
+                status.finish(SearchResult { info, best, error });
+                workers
+            })
+        };
+        Self { status, ponder, handle }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.status.result().is_some()
+    }
+
+    /// Cancels the search and waits for the workers to finish their iterations.
+    pub fn stop(self) -> (Vec<Worker>, SearchResult) {
+        self.status.cancel();
+        let workers = self.handle.join().unwrap();
+        let result = self.status.result().clone().unwrap();
+        (workers, result)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tree at the start of the game with the first `n` children of the
+    /// root added.
+    fn tree(n: usize) -> Tree {
+        let state = Game::new();
+        let mut tree = Tree::new(&state, &MctsConfig::default());
+        for edge in 0..n {
+            let mut child = state.clone();
+            child.make_move(tree.root().children[edge].0);
+            let (id, _) = tree.insert(&child);
+            tree.nodes[tree.root].children[edge].1 = Some(id);
+        }
+        tree
+    }
+
+    fn set_proofs(tree: &Tree, proofs: impl Fn(usize) -> Proof) {
+        for (edge, &(_, child)) in tree.root().children.iter().enumerate() {
+            if let Some(child) = child {
+                tree.nodes[child].set_proof(proofs(edge));
+            }
+        }
+    }
+
+    #[test]
+    fn winning_child_proves_loss() {
+        let tree = tree(81);
+        set_proofs(&tree, |edge| if edge == 40 { Proof::Win } else { Proof::Unknown });
+        assert!(prove(&tree, tree.root));
+        assert_eq!(tree.root().proof(), Proof::Loss);
+    }
+
+    #[test]
+    fn proven_children_with_a_draw_prove_draw() {
+        let tree = tree(81);
+        set_proofs(&tree, |edge| if edge % 2 == 0 { Proof::Draw } else { Proof::Loss });
+        assert!(prove(&tree, tree.root));
+        assert_eq!(tree.root().proof(), Proof::Draw);
+    }
+
+    #[test]
+    fn unexpanded_child_leaves_node_unproven() {
+        let tree = tree(80);
+        set_proofs(&tree, |_| Proof::Loss);
+        assert!(!prove(&tree, tree.root));
+        assert_eq!(tree.root().proof(), Proof::Unknown);
+    }
+}
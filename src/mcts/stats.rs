@@ -0,0 +1,193 @@
+use std::sync::RwLock;
+use pyo3::prelude::*;
+use crate::env::move_to_action;
+use crate::budget::SearchInfo;
+use super::config::MctsConfig;
+use super::node::Wdl;
+use super::root::{all_root_children, root_visits};
+use super::tree::Tree;
+
+/// Win, draw and loss probabilities of a player with 95% Wilson score
+/// intervals.
+#[pyclass]
+#[derive(Clone, Debug)]
+pub struct WdlStats {
+    #[pyo3(get)]
+    pub visits: u32,
+    #[pyo3(get)]
+    pub win: f32,
+    #[pyo3(get)]
+    pub draw: f32,
+    #[pyo3(get)]
+    pub loss: f32,
+    #[pyo3(get)]
+    pub win_ci: (f32, f32),
+    #[pyo3(get)]
+    pub draw_ci: (f32, f32),
+    #[pyo3(get)]
+    pub loss_ci: (f32, f32),
+}
+
+impl WdlStats {
+    /// Probabilities of `wdl` over `visits` results, `None` without any.
+    pub fn new(wdl: Wdl, visits: u32) -> Option<Self> {
+        if visits == 0 {
+            return None;
+        }
+        let n = visits as f32;
+        let (win, draw, loss) = (wdl.win / n, wdl.draw / n, wdl.loss / n);
+        Some(Self {
+            visits,
+            win,
+            draw,
+            loss,
+            win_ci: wilson(win, n),
+            draw_ci: wilson(draw, n),
+            loss_ci: wilson(loss, n),
+        })
+    }
+}
+
+#[pymethods]
+impl WdlStats {
+    fn __repr__(&self) -> String {
+        format!("WdlStats(visits={}, win={:.3}, draw={:.3}, loss={:.3})",
+            self.visits, self.win, self.draw, self.loss)
+    }
+}
+
+/// 95% Wilson score interval of a proportion `p` observed over `n` trials.
+fn wilson(p: f32, n: f32) -> (f32, f32) {
+    const Z: f32 = 1.96;
+    let z2 = Z * Z;
+    let center = (p + z2 / (2.0 * n)) / (1.0 + z2 / n);
+    let half = Z * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).max(0.0).sqrt() / (1.0 + z2 / n);
+    ((center - half).max(0.0), (center + half).min(1.0))
+}
+
+/// Statistics of a root child, merged across trees.
+#[pyclass]
+#[derive(Clone, Debug)]
+pub struct ChildStats {
+    #[pyo3(get)]
+    pub action: u8,
+    #[pyo3(get)]
+    pub visits: u32,
+    /// Mean reward of the player to move at the root, `None` if unvisited.
+    #[pyo3(get)]
+    pub mean: Option<f32>,
+    /// UCT score at the root, `None` if unvisited.
+    #[pyo3(get)]
+    pub uct: Option<f32>,
+    /// Prior probability of the move given by the evaluator, uniform
+    /// without PUCT or before the root has been evaluated.
+    #[pyo3(get)]
+    pub prior: f32,
+    /// "win", "loss" or "draw" for the player to move at the root if proven.
+    #[pyo3(get)]
+    pub proof: Option<&'static str>,
+    /// Deepest ply from the root reached through the move, 0 if unvisited.
+    #[pyo3(get)]
+    pub depth: u32,
+    /// Results of the player to move at the root, `None` if unvisited.
+    #[pyo3(get)]
+    pub wdl: Option<WdlStats>,
+}
+
+#[pymethods]
+impl ChildStats {
+    fn __repr__(&self) -> String {
+        let format = |value: Option<f32>| value.map_or("None".to_string(), |value| format!("{:.3}", value));
+        format!("ChildStats(action={}, visits={}, mean={}, uct={}, prior={:.3}, proof={}, depth={})",
+            self.action, self.visits, format(self.mean), format(self.uct), self.prior, self.proof.unwrap_or("None"),
+            self.depth)
+    }
+}
+
+/// Root children and the work of the last search on the budget.
+#[pyclass]
+#[derive(Clone, Debug)]
+pub struct SearchStats {
+    #[pyo3(get)]
+    pub children: Vec<ChildStats>,
+    #[pyo3(get)]
+    pub iterations: u64,
+    #[pyo3(get)]
+    pub rollouts_per_s: f32,
+    #[pyo3(get)]
+    pub max_depth: u32,
+    #[pyo3(get)]
+    pub avg_depth: f32,
+    #[pyo3(get)]
+    pub elapsed_s: f32,
+}
+
+#[pymethods]
+impl SearchStats {
+    fn __repr__(&self) -> String {
+        format!("SearchStats(children={}, iterations={}, rollouts_per_s={:.1}, max_depth={}, avg_depth={:.2}, \
+            elapsed_s={:.3})",
+            self.children.len(), self.iterations, self.rollouts_per_s, self.max_depth, self.avg_depth,
+            self.elapsed_s)
+    }
+}
+
+pub fn stats(trees: &[RwLock<Tree>], config: &MctsConfig, search: &SearchInfo) -> SearchStats {
+    let parent_visits = root_visits(trees);
+    let root_children = all_root_children(trees);
+    let uniform = 1.0 / root_children.len() as f32;
+    let priors: Vec<f32> = {
+        let tree = trees[0].read().unwrap();
+        (0..root_children.len()).map(|i| tree.root().prior(i).unwrap_or(uniform)).collect()
+    };
+    let mut children: Vec<ChildStats> = root_children
+        .into_iter()
+        .zip(priors)
+        .map(|(child, prior)| {
+            let visited = child.visits > 0;
+            ChildStats {
+                action: move_to_action(child.action),
+                visits: child.visits,
+                mean: visited.then(|| child.mean(config.draw_value)),
+                uct: visited.then(|| child.uct_value(parent_visits, config.exploration, config.draw_value)),
+                prior,
+                proof: child.proof.name(),
+                depth: 0,
+                wdl: WdlStats::new(child.wdl, child.visits),
+            }
+        })
+        .collect();
+    for tree in trees {
+        let tree = tree.read().unwrap();
+        let mut heights = vec![None; tree.size()];
+        for (stats, &(_, child)) in children.iter_mut().zip(&tree.root().children) {
+            if let Some(child) = child {
+                stats.depth = stats.depth.max(1 + height(&tree, child, &mut heights));
+            }
+        }
+    }
+    SearchStats {
+        children,
+        iterations: search.iterations,
+        rollouts_per_s: search.rollouts_per_s(),
+        max_depth: search.max_depth,
+        avg_depth: search.avg_depth(),
+        elapsed_s: search.elapsed_s,
+    }
+}
+
+/// Length of the longest path down from `node`, remembered in `heights`
+/// as transpositions may reach a node more than once.
+fn height(tree: &Tree, node: usize, heights: &mut [Option<u32>]) -> u32 {
+    if let Some(height) = heights[node] {
+        return height;
+    }
+    let height = tree.nodes[node].children
+        .iter()
+        .filter_map(|&(_, child)| child)
+        .map(|child| 1 + height(tree, child, heights))
+        .max()
+        .unwrap_or(0);
+    heights[node] = Some(height);
+    height
+}
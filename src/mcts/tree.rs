@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+use crate::game::{Game, Move};
+use super::config::{MctsConfig, Selection};
+use super::node::Node;
+
+/// A search tree grown from the root position. Nodes refer to each other by
+/// their index in `nodes`.
+///
+/// With transpositions tracked it is really a directed acyclic graph: every
+/// position is stored once, whatever the move order that reached it.
+pub struct Tree {
+    pub nodes: Vec<Node>,
+    pub root: usize,
+    memory: usize,
+    /// Whether nodes keep AMAF statistics.
+    pub amaf: bool,
+    /// Whether nodes keep prior probabilities of their children.
+    priors: bool,
+    /// Node of every position by hash, `None` unless transpositions are
+    /// tracked.
+    table: Option<HashMap<u64, usize>>,
+    /// Number of times the nodes were renumbered, which invalidates node
+    /// indices held outside the tree.
+    pub generation: u64,
+    /// Dirichlet noise for each root child, empty without noise.
+    pub noise: Vec<f32>,
+}
+
+impl Tree {
+    pub fn new(state: &Game, config: &MctsConfig) -> Self {
+        let mut tree = Self {
+            nodes: Vec::new(),
+            root: 0,
+            memory: 0,
+            amaf: config.rave.is_some(),
+            priors: config.selection == Selection::Puct,
+            table: config.transpositions.then(HashMap::new),
+            generation: 0,
+            noise: Vec::new(),
+        };
+        tree.insert(state);
+        tree
+    }
+
+    pub fn root(&self) -> &Node {
+        &self.nodes[self.root]
+    }
+
+    /// Number of nodes in the tree. Every node is reachable from the root.
+    pub fn size(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Approximate number of bytes used by the tree.
+    pub fn memory(&self) -> usize {
+        self.memory + self.table.as_ref().map_or(0, |table| {
+            table.capacity() * (std::mem::size_of::<(u64, usize)>() + 1)
+        })
+    }
+
+    /// Node of `state`: the one already in the table, if transpositions are
+    /// tracked and the position has been reached before, or else a new one.
+    /// Returns the node and whether it was found in the table.
+    pub fn insert(&mut self, state: &Game) -> (usize, bool) {
+        let hash = self.table.as_ref().map(|_| state.hash());
+        if let Some(&id) = hash.and_then(|hash| self.table.as_ref().unwrap().get(&hash)) {
+            return (id, true);
+        }
+        let id = self.nodes.len();
+        self.push(Node::new(state, self.amaf, self.priors, hash));
+        if let (Some(table), Some(hash)) = (self.table.as_mut(), hash) {
+            table.insert(hash, id);
+        }
+        (id, false)
+    }
+
+    /// Moves the root along `m`. `state` is the position after the move.
+    /// The root noise no longer applies and is dropped. Returns the number
+    /// of nodes reclaimed.
+    pub fn move_root(&mut self, m: Move, state: &Game) -> usize {
+        self.noise.clear();
+        let new_root = self.root().children.iter()
+            .find_map(|(a, node)| if *a == m { Some(node) } else { None })
+            .unwrap();
+        self.root = match new_root {
+            Some(new_root) => *new_root,
+            None => self.insert(state).0,
+        };
+        self.compact()
+    }
+
+    /// Drops every node not reachable from the root and renumbers the rest.
+    /// Returns the number of nodes reclaimed.
+    pub fn compact(&mut self) -> usize {
+        let before = self.nodes.len();
+
+        // breadth-first order of the reachable nodes, which becomes their new
+        // index; a transposition is reached more than once but kept once
+        let mut remap = vec![usize::MAX; before];
+        let mut order = vec![self.root];
+        remap[self.root] = 0;
+        let mut i = 0;
+        while i < order.len() {
+            for &(_, child) in &self.nodes[order[i]].children {
+                if let Some(child) = child {
+                    if remap[child] == usize::MAX {
+                        remap[child] = order.len();
+                        order.push(child);
+                    }
+                }
+            }
+            i += 1;
+        }
+
+        let mut old: Vec<Option<Node>> = std::mem::take(&mut self.nodes)
+            .into_iter()
+            .map(Some)
+            .collect();
+        self.nodes = order.into_iter()
+            .map(|id| {
+                let mut node = old[id].take().unwrap();
+                for (_, child) in node.children.iter_mut() {
+                    *child = child.map(|child| remap[child]);
+                }
+                node
+            })
+            .collect();
+        self.root = 0;
+        self.generation += 1;
+        self.memory = self.nodes.iter().map(Node::memory).sum();
+        if let Some(table) = self.table.as_mut() {
+            *table = self.nodes.iter().enumerate().map(|(id, node)| (node.hash, id)).collect();
+        }
+
+        before - self.nodes.len()
+    }
+
+    fn push(&mut self, node: Node) {
+        self.memory += node.memory();
+        self.nodes.push(node);
+    }
+
+    /// Whether the tree has reached `max_nodes` or `max_memory_bytes`.
+    pub fn is_full(&self, config: &MctsConfig) -> bool {
+        config.max_nodes.is_some_and(|max| self.nodes.len() >= max)
+            || config.max_memory_bytes.is_some_and(|max| self.memory() >= max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use super::*;
+
+    /// Plays `moves` from the root, the node of `state`, adding a node for
+    /// each. Returns the last node.
+    fn add_line(tree: &mut Tree, state: &Game, moves: &[Move]) -> usize {
+        let mut n = tree.root;
+        let mut state = state.clone();
+        for &action in moves {
+            let edge = tree.nodes[n].children.iter().position(|&(a, _)| a == action).unwrap();
+            state.make_move(action);
+            let (child, _) = tree.insert(&state);
+            tree.nodes[n].children[edge].1 = Some(child);
+            n = child;
+        }
+        n
+    }
+
+    /// Checks that the node `n` holds `state` and so do its descendants,
+    /// collecting the nodes reached.
+    fn check(tree: &Tree, n: usize, state: &Game, reached: &mut HashSet<usize>) {
+        assert_eq!(tree.nodes[n].hash, state.hash());
+        reached.insert(n);
+        for &(action, child) in &tree.nodes[n].children {
+            if let Some(child) = child {
+                let mut state = state.clone();
+                state.make_move(action);
+                check(tree, child, &state, reached);
+            }
+        }
+    }
+
+    #[test]
+    fn compact_keeps_reachable_nodes() {
+        let config = MctsConfig { transpositions: true, ..MctsConfig::default() };
+        let state = Game::new();
+        let mut tree = Tree::new(&state, &config);
+        let (a, b) = (Move((0, 0), (1, 1)), Move((1, 1), (0, 0)));
+        let (c, d) = (Move((0, 0), (2, 2)), Move((2, 2), (0, 0)));
+
+        // unreachable nodes between the reachable ones
+        let mut orphan = state.clone();
+        orphan.make_move(Move((2, 2), (2, 2)));
+        tree.insert(&orphan);
+        let end = add_line(&mut tree, &state, &[a, b, c, d]);
+        let mut other = state.clone();
+        other.make_move(Move((1, 1), (1, 1)));
+        tree.insert(&other);
+        // the last move of the second line is a transposition
+        assert_eq!(add_line(&mut tree, &state, &[c, d, a, b]), end);
+        assert_eq!(tree.size(), 10);
+
+        assert_eq!(tree.compact(), 2);
+        assert_eq!(tree.size(), 8);
+        assert_eq!(tree.root, 0);
+        let mut reached = HashSet::new();
+        check(&tree, tree.root, &state, &mut reached);
+        assert_eq!(reached.len(), tree.size());
+
+        let table = tree.table.as_ref().unwrap();
+        assert_eq!(table.len(), tree.size());
+        for (id, node) in tree.nodes.iter().enumerate() {
+            assert_eq!(table[&node.hash], id);
+        }
+        assert!(!tree.insert(&orphan).1);
+    }
+}
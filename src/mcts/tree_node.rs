@@ -0,0 +1,174 @@
+use std::sync::{Arc, RwLock};
+use pyo3::prelude::*;
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use crate::env::{UTTTEnvImpl, action_to_move, move_to_action};
+use crate::game::Game;
+use super::config::MctsConfig;
+use super::node::Node;
+use super::stats::WdlStats;
+use super::tree::Tree;
+
+/// Read-only handle to a node of an `MCTS` tree. The statistics are read
+/// when asked for, so they follow a search that is still running. Moving
+/// the root or compacting the tree renumbers its nodes, after which the
+/// handle raises `RuntimeError`.
+#[pyclass]
+#[derive(Clone)]
+pub struct TreeNode {
+    trees: Arc<[RwLock<Tree>]>,
+    tree: usize,
+    generation: u64,
+    node: usize,
+    /// Parent node and the index of the edge leading here, `None` at the root.
+    parent: Option<(usize, usize)>,
+    state: Game,
+    config: MctsConfig,
+}
+
+impl TreeNode {
+    pub fn root(trees: Arc<[RwLock<Tree>]>, tree: usize, state: Game, config: MctsConfig) -> Self {
+        let (generation, node) = {
+            let tree = trees[tree].read().unwrap();
+            (tree.generation, tree.root)
+        };
+        Self { trees, tree, generation, node, parent: None, state, config }
+    }
+
+    /// Runs `f` on the node, unless the tree has been renumbered.
+    fn with_node<T>(&self, f: impl FnOnce(&Tree, &Node) -> T) -> PyResult<T> {
+        let tree = self.trees[self.tree].read().unwrap();
+        if tree.generation != self.generation {
+            return Err(PyRuntimeError::new_err("the tree has changed since the node was taken"));
+        }
+        Ok(f(&tree, &tree.nodes[self.node]))
+    }
+
+    /// Handle to the child along the edge `i`, `None` if it is not expanded.
+    fn child_at(&self, tree: &Tree, i: usize) -> Option<TreeNode> {
+        let (m, child) = tree.nodes[self.node].children[i];
+        child.map(|child| {
+            let mut state = self.state.clone();
+            state.make_move(m);
+            TreeNode {
+                trees: self.trees.clone(),
+                tree: self.tree,
+                generation: self.generation,
+                node: child,
+                parent: Some((self.node, i)),
+                state,
+                config: self.config.clone(),
+            }
+        })
+    }
+}
+
+#[pymethods]
+impl TreeNode {
+    /// Action leading to the node, `None` at the root.
+    #[getter]
+    pub fn action(&self) -> PyResult<Option<u8>> {
+        match self.parent {
+            None => Ok(None),
+            Some((parent, i)) => self.with_node(|tree, _| Some(move_to_action(tree.nodes[parent].children[i].0))),
+        }
+    }
+
+    #[getter]
+    pub fn visits(&self) -> PyResult<u32> {
+        self.with_node(|_, node| node.visits())
+    }
+
+    /// Mean reward of the player who moved into the node, `None` if unvisited.
+    #[getter]
+    pub fn value(&self) -> PyResult<Option<f32>> {
+        self.with_node(|_, node| {
+            (node.visits() > 0).then(|| node.reward(self.config.draw_value) / node.visits() as f32)
+        })
+    }
+
+    /// UCT score of the node as seen from its parent, `None` at the root or
+    /// if unvisited.
+    #[getter]
+    pub fn uct(&self) -> PyResult<Option<f32>> {
+        self.with_node(|tree, node| {
+            let (parent, i) = self.parent?;
+            let parent = &tree.nodes[parent];
+            (node.visits() > 0 && parent.visits() > 0).then(|| parent.child_uct_value(i, node, &self.config))
+        })
+    }
+
+    /// Prior probability of the move leading to the node, `None` at the root
+    /// or without PUCT.
+    #[getter]
+    pub fn prior(&self) -> PyResult<Option<f32>> {
+        self.with_node(|tree, _| {
+            let (parent, i) = self.parent?;
+            tree.nodes[parent].prior(i)
+        })
+    }
+
+    /// Wins, draws and losses of the player who moved into the node.
+    #[getter]
+    pub fn wdl(&self) -> PyResult<Option<WdlStats>> {
+        self.with_node(|_, node| WdlStats::new(node.wdl(), node.visits()))
+    }
+
+    /// "win", "loss" or "draw" for the player who moved into the node if
+    /// proven by the solver.
+    #[getter]
+    pub fn proof(&self) -> PyResult<Option<&'static str>> {
+        self.with_node(|_, node| node.proof().name())
+    }
+
+    pub fn is_terminal(&self) -> PyResult<bool> {
+        self.with_node(|_, node| node.is_terminal())
+    }
+
+    pub fn is_fully_expanded(&self) -> PyResult<bool> {
+        self.with_node(|_, node| node.fully_expanded())
+    }
+
+    /// Expanded children in action order.
+    pub fn children(&self) -> PyResult<Vec<TreeNode>> {
+        self.with_node(|tree, node| {
+            (0..node.children.len()).filter_map(|i| self.child_at(tree, i)).collect()
+        })
+    }
+
+    /// Child reached by `action`, `None` if it is not expanded.
+    pub fn child(&self, action: u8) -> PyResult<Option<TreeNode>> {
+        if action >= 81 {
+            return Err(PyValueError::new_err(format!("action {} is out of range", action)));
+        }
+        let m = action_to_move(action);
+        self.with_node(|tree, node| {
+            let i = node.children.iter().position(|&(a, _)| a == m)
+                .ok_or_else(|| PyValueError::new_err(format!("action {} is not valid here", action)))?;
+            Ok(self.child_at(tree, i))
+        })?
+    }
+
+    /// Node reached by playing `actions` from this one, `None` if the line
+    /// leaves the tree.
+    pub fn descend(&self, actions: Vec<u8>) -> PyResult<Option<TreeNode>> {
+        let mut node = self.clone();
+        for action in actions {
+            match node.child(action)? {
+                Some(child) => node = child,
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(node))
+    }
+
+    /// Position at the node.
+    pub fn position(&self) -> UTTTEnvImpl {
+        UTTTEnvImpl { game: self.state.clone() }
+    }
+
+    fn __repr__(&self) -> PyResult<String> {
+        let action = self.action()?.map_or("None".to_string(), |action| action.to_string());
+        let value = self.value()?.map_or("None".to_string(), |value| format!("{:.3}", value));
+        Ok(format!("TreeNode(action={}, visits={}, value={})", action, self.visits()?, value))
+    }
+}
@@ -1,51 +1,105 @@
+use std::sync::Arc;
 use pyo3::prelude::*;
-use rand::seq::SliceRandom;
+use rand::rngs::StdRng;
 use crate::env::UTTTEnvImpl;
+use crate::budget::{Budget, SearchInfo};
+use crate::game::Move;
+use crate::rollout::{RolloutPolicy, rollout};
 
 #[pyclass]
+#[allow(clippy::upper_case_acronyms)]
 pub struct PMCTS {
-    time_budget: std::time::Duration,
+    budget: Budget,
+    last_search: SearchInfo,
+    rng: StdRng,
+    rollout_policy: Arc<dyn RolloutPolicy>,
+    /// Moves of the last rollout, starting with the action it evaluates.
+    playout: Vec<Move>,
 }
 
 #[pymethods]
 impl PMCTS {
     #[new]
-    pub fn new(time_budget_s: f32) -> Self {
-        PMCTS { time_budget: std::time::Duration::from_secs_f32(time_budget_s) }
+    #[pyo3(signature = (
+        time_budget_s = None,
+        *,
+        iteration_budget = None,
+        rollout_budget = None,
+        rollout_policy = "uniform",
+        rollout_epsilon = 0.0,
+        rollout_temperature = 0.1,
+        seed = None,
+    ))]
+    pub fn new(
+        time_budget_s: Option<f32>,
+        iteration_budget: Option<u64>,
+        rollout_budget: Option<u64>,
+        rollout_policy: &str,
+        rollout_epsilon: f64,
+        rollout_temperature: f32,
+        seed: Option<u64>,
+    ) -> PyResult<Self> {
+        Ok(PMCTS {
+            budget: Budget::new(time_budget_s, iteration_budget, rollout_budget, None)?,
+            last_search: SearchInfo::default(),
+            rng: crate::rng::from_seed(seed),
+            rollout_policy: crate::rollout::from_name(rollout_policy, rollout_epsilon, rollout_temperature)?,
+            playout: Vec::new(),
+        })
     }
 
-    pub fn run(&self, game: UTTTEnvImpl) -> u8 {
+    /// Searches until the budget is exhausted and returns the chosen action.
+    /// The GIL is released for the duration of the search.
+    pub fn run(&mut self, py: Python, game: UTTTEnvImpl) -> u8 {
+        py.allow_threads(|| self.search(game))
+    }
+
+    /// Work done by the last call to `run`. Every iteration plays one rollout
+    /// per valid action; the budget may run out in the middle of one.
+    pub fn last_search(&self) -> SearchInfo {
+        self.last_search.clone()
+    }
+}
+
+impl PMCTS {
+    fn search(&mut self, game: UTTTEnvImpl) -> u8 {
         let actions = game.valid_actions();
         let mut rewards = vec![0.0; actions.len()];
+        let mut rollouts = vec![0; actions.len()];
         let start = std::time::Instant::now();
-        while start.elapsed() < self.time_budget {
+        let mut info = SearchInfo::default();
+        'search: loop {
             for (i, action) in actions.iter().enumerate() {
+                if self.budget.exhausted(start, &info) {
+                    break 'search;
+                }
                 let mut game = game.clone();
                 game.step(*action);
                 rewards[i] += self.rollout(game);
+                rollouts[i] += 1;
+                info.rollouts += 1;
             }
+            info.iterations += 1;
         }
-        *actions
-            .iter()
-            .zip(rewards)
-            .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
-            .unwrap().0
+        info.elapsed_s = start.elapsed().as_secs_f32();
+        self.last_search = info;
+        // the actions left out of an unfinished iteration have one rollout
+        // less, so they are compared by their mean reward
+        let mean = |i: usize| if rollouts[i] > 0 { rewards[i] / rollouts[i] as f32 } else { f32::NEG_INFINITY };
+        actions[(0..actions.len())
+            .max_by(|&a, &b| mean(a).partial_cmp(&mean(b)).unwrap())
+            .unwrap()]
     }
-}
 
-impl PMCTS {
-    fn rollout(&self, mut game: UTTTEnvImpl) -> f32 {
-        let mut rng = rand::thread_rng();
-        let enemy = game.current_player();
-        while !game.done() {
-            let actions = game.valid_actions();
-            let action = actions.choose(&mut rng).unwrap();
-            game.step(*action);
-            
-        }
-        match enemy {
-            0 => return -game.reward(),
-            _ => return game.reward(),
+    fn rollout(&mut self, game: UTTTEnvImpl) -> f32 {
+        let enemy = game.game.current_player();
+        self.playout.clear();
+        self.playout.extend(game.game.last_move());
+        let winner = rollout(game.game, &*self.rollout_policy, &mut self.rng, &mut self.playout, None).winner();
+        self.rollout_policy.update(&self.playout, enemy.other(), winner);
+        match winner {
+            None => 0.0,
+            Some(winner) => if winner == enemy { -1.0 } else { 1.0 },
         }
     }
 }
@@ -0,0 +1,10 @@
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+
+/// Seeded generator if a seed is given, otherwise one seeded from the OS.
+pub fn from_seed(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    }
+}
@@ -0,0 +1,137 @@
+use std::sync::atomic::{AtomicU8, AtomicU32, Ordering};
+use rand::Rng;
+use rand::rngs::StdRng;
+use crate::atomic::AtomicF32;
+use crate::env::{move_to_action, action_to_move};
+use crate::game::{Game, Player, Move};
+use super::{RolloutPolicy, Uniform};
+
+fn player_index(player: Player) -> usize {
+    match player {
+        Player::X => 0,
+        Player::O => 1,
+    }
+}
+
+/// Reward of `player` in a game won by `winner`.
+fn reward(player: Player, winner: Option<Player>) -> f32 {
+    match winner {
+        None => 0.5,
+        Some(winner) => if winner == player { 1.0 } else { 0.0 },
+    }
+}
+
+/// Move-Average Sampling Technique: keeps the mean reward of every action
+/// for each player over all iterations it was played in, in the tree or in
+/// a rollout, and picks moves by Gibbs sampling on those means.
+#[derive(Debug)]
+pub struct Mast {
+    temperature: f32,
+    visits: [[AtomicU32; 81]; 2],
+    reward: [[AtomicF32; 81]; 2],
+}
+
+impl Mast {
+    pub fn new(temperature: f32) -> Self {
+        Self {
+            temperature,
+            visits: std::array::from_fn(|_| std::array::from_fn(|_| AtomicU32::new(0))),
+            reward: std::array::from_fn(|_| std::array::from_fn(|_| AtomicF32::new(0.0))),
+        }
+    }
+
+    /// Mean reward of `action` for `player`. Actions never played count as
+    /// wins so that they get tried.
+    fn mean(&self, player: usize, action: usize) -> f32 {
+        match self.visits[player][action].load(Ordering::Relaxed) {
+            0 => 1.0,
+            visits => self.reward[player][action].load() / visits as f32,
+        }
+    }
+}
+
+impl RolloutPolicy for Mast {
+    fn choose(&self, state: &Game, moves: &[Move], rng: &mut StdRng) -> Move {
+        let player = player_index(state.current_player());
+        let weights: Vec<f32> = moves.iter()
+            .map(|&m| (self.mean(player, move_to_action(m) as usize) / self.temperature).exp())
+            .collect();
+        let mut target = rng.gen::<f32>() * weights.iter().sum::<f32>();
+        for (&m, weight) in moves.iter().zip(weights) {
+            if target < weight {
+                return m;
+            }
+            target -= weight;
+        }
+        *moves.last().unwrap()
+    }
+
+    fn update(&self, moves: &[Move], first: Player, winner: Option<Player>) {
+        let mut player = first;
+        for &m in moves {
+            let action = move_to_action(m) as usize;
+            self.visits[player_index(player)][action].fetch_add(1, Ordering::Relaxed);
+            self.reward[player_index(player)][action].add(reward(player, winner));
+            player = player.other();
+        }
+    }
+
+    fn reset(&self) {
+        self.visits.iter().flatten().for_each(|visits| visits.store(0, Ordering::Relaxed));
+        self.reward.iter().flatten().for_each(|reward| reward.store(0.0));
+    }
+}
+
+/// Last-Good-Reply with forgetting: remembers, for each player and each
+/// move of the opponent, the reply the player made in the last iteration
+/// they won, and forgets it once the player loses an iteration with it.
+/// Plays the remembered reply to the last move when it is valid and a
+/// uniformly random move otherwise.
+#[derive(Debug)]
+pub struct LastGoodReply {
+    /// Action of the reply, `NONE` if there is none.
+    replies: [[AtomicU8; 81]; 2],
+}
+
+impl LastGoodReply {
+    const NONE: u8 = u8::MAX;
+
+    pub fn new() -> Self {
+        Self {
+            replies: std::array::from_fn(|_| std::array::from_fn(|_| AtomicU8::new(Self::NONE))),
+        }
+    }
+}
+
+impl RolloutPolicy for LastGoodReply {
+    fn choose(&self, state: &Game, moves: &[Move], rng: &mut StdRng) -> Move {
+        let reply = state.last_move()
+            .map(|last| self.replies[player_index(state.current_player())][move_to_action(last) as usize]
+                .load(Ordering::Relaxed))
+            .filter(|&reply| reply != Self::NONE)
+            .map(action_to_move);
+        match reply {
+            Some(reply) if moves.contains(&reply) => reply,
+            _ => Uniform.choose(state, moves, rng),
+        }
+    }
+
+    fn update(&self, moves: &[Move], first: Player, winner: Option<Player>) {
+        let Some(winner) = winner else { return };
+        let mut player = first.other();
+        for pair in moves.windows(2) {
+            let (last, reply) = (move_to_action(pair[0]) as usize, move_to_action(pair[1]));
+            let stored = &self.replies[player_index(player)][last];
+            if player == winner {
+                stored.store(reply, Ordering::Relaxed);
+            } else {
+                let _ = stored.compare_exchange(reply, Self::NONE, Ordering::Relaxed, Ordering::Relaxed);
+            }
+            player = player.other();
+        }
+    }
+
+    fn reset(&self) {
+        self.replies.iter().flatten().for_each(|reply| reply.store(Self::NONE, Ordering::Relaxed));
+    }
+}
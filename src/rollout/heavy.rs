@@ -0,0 +1,132 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use crate::game::{Game, Player, Move, get_winner};
+use super::RolloutPolicy;
+
+/// Plays a move that wins the game if there is one. The anti-decisive
+/// variant also avoids moves after which the opponent can win the game.
+/// Otherwise moves are uniformly random.
+#[derive(Debug)]
+pub struct Decisive {
+    pub anti: bool,
+}
+
+impl RolloutPolicy for Decisive {
+    fn choose(&self, state: &Game, moves: &[Move], rng: &mut StdRng) -> Move {
+        if let Some(&m) = moves.iter().find(|&&m| wins_game(state, m)) {
+            return m;
+        }
+        let deciding = if self.anti {
+            deciding_boards(state, state.current_player().other())
+        } else {
+            [[false; 3]; 3]
+        };
+        if deciding.iter().flatten().any(|&deciding| deciding) {
+            let decides = |(x, y): (u8, u8)| deciding[x as usize][y as usize];
+            let safe: Vec<Move> = moves.iter()
+                .copied()
+                .filter(|&m| {
+                    let mut state = state.clone();
+                    state.make_move(m);
+                    // the opponent is sent to a board that cannot decide the game
+                    if !state.small_board(m.1).is_over() && !decides(m.1) {
+                        return true;
+                    }
+                    !state.valid_moves().into_iter().any(|m| decides(m.0) && wins_game(&state, m))
+                })
+                .collect();
+            if let Some(&m) = safe.choose(rng) {
+                return m;
+            }
+        }
+        *moves.choose(rng).unwrap()
+    }
+}
+
+/// Prefers moves that win a small board, then moves that block the
+/// opponent from winning one, and avoids sending the opponent to a finished
+/// small board, which gives them a free move. Ties are broken at random.
+#[derive(Debug)]
+pub struct SmallBoardAware;
+
+impl SmallBoardAware {
+    fn score(state: &Game, m: Move) -> i32 {
+        let player = state.current_player();
+        let mut score = 0;
+        if wins_small_board(state, m, player) {
+            score += 4;
+        }
+        if wins_small_board(state, m, player.other()) {
+            score += 2;
+        }
+        let target = state.small_board(m.1);
+        let target_over = if m.0 == m.1 {
+            let mut target = target.clone();
+            target.place(player, m.1);
+            target.is_over()
+        } else {
+            target.is_over()
+        };
+        if target_over {
+            score -= 3;
+        }
+        score
+    }
+}
+
+impl RolloutPolicy for SmallBoardAware {
+    fn choose(&self, state: &Game, moves: &[Move], rng: &mut StdRng) -> Move {
+        let scores: Vec<i32> = moves.iter().map(|&m| Self::score(state, m)).collect();
+        let best = *scores.iter().max().unwrap();
+        let best_moves: Vec<Move> = moves.iter()
+            .zip(&scores)
+            .filter(|&(_, &score)| score == best)
+            .map(|(&m, _)| m)
+            .collect();
+        *best_moves.choose(rng).unwrap()
+    }
+}
+
+/// Whether `m` would win its small board for `player`, which must not have
+/// been won already.
+fn wins_small_board(state: &Game, m: Move, player: Player) -> bool {
+    let board = state.small_board(m.0);
+    board.get_winner().is_none() && {
+        let mut board = board.clone();
+        board.place(player, m.1);
+        board.get_winner() == Some(player)
+    }
+}
+
+/// Whether `m` wins the game for the player to move.
+fn wins_game(state: &Game, m: Move) -> bool {
+    let player = state.current_player();
+    wins_small_board(state, m, player) && {
+        let mut state = state.clone();
+        state.make_move(m);
+        state.winner() == Some(player)
+    }
+}
+
+/// Unfinished small boards whose win would win the game for `player`.
+/// Only moves in these boards need to be looked at when searching for a
+/// winning move.
+fn deciding_boards(state: &Game, player: Player) -> [[bool; 3]; 3] {
+    let mut meta = [[None; 3]; 3];
+    for (i, row) in meta.iter_mut().enumerate() {
+        for (j, cell) in row.iter_mut().enumerate() {
+            *cell = state.small_board((i as u8, j as u8)).get_winner();
+        }
+    }
+    let mut deciding = [[false; 3]; 3];
+    for (i, row) in deciding.iter_mut().enumerate() {
+        for (j, deciding) in row.iter_mut().enumerate() {
+            if !state.small_board((i as u8, j as u8)).is_over() {
+                let mut meta = meta;
+                meta[i][j] = Some(player);
+                *deciding = get_winner(&meta) == Some(player);
+            }
+        }
+    }
+    deciding
+}
@@ -0,0 +1,115 @@
+mod heavy;
+mod adaptive;
+
+use std::fmt::Debug;
+use std::sync::Arc;
+use pyo3::prelude::*;
+use pyo3::exceptions::PyValueError;
+use rand::Rng;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use crate::game::{Game, Player, Move};
+
+pub use heavy::{Decisive, SmallBoardAware};
+pub use adaptive::{Mast, LastGoodReply};
+
+/// Picks the moves of a rollout. Adaptive policies learn from the results
+/// of the search as it goes, the tables they learn are shared by every
+/// thread of the searcher that owns the policy.
+pub trait RolloutPolicy: Send + Sync + Debug {
+    /// Picks the next move among `moves`, the valid moves of `state`.
+    fn choose(&self, state: &Game, moves: &[Move], rng: &mut StdRng) -> Move;
+
+    /// Learns from a finished iteration, in which `moves` were played in
+    /// turn starting with `first` and `winner` won the game.
+    fn update(&self, _moves: &[Move], _first: Player, _winner: Option<Player>) {}
+
+    /// Forgets everything learned.
+    fn reset(&self) {}
+}
+
+/// Uniformly random moves.
+#[derive(Debug)]
+pub struct Uniform;
+
+impl RolloutPolicy for Uniform {
+    fn choose(&self, _state: &Game, moves: &[Move], rng: &mut StdRng) -> Move {
+        *moves.choose(rng).unwrap()
+    }
+}
+
+/// Uniformly random move with probability `epsilon`, otherwise the move of
+/// `policy`.
+#[derive(Debug)]
+pub struct EpsilonMixture {
+    pub epsilon: f64,
+    pub policy: Box<dyn RolloutPolicy>,
+}
+
+impl RolloutPolicy for EpsilonMixture {
+    fn choose(&self, state: &Game, moves: &[Move], rng: &mut StdRng) -> Move {
+        if rng.gen_bool(self.epsilon) {
+            Uniform.choose(state, moves, rng)
+        } else {
+            self.policy.choose(state, moves, rng)
+        }
+    }
+
+    fn update(&self, moves: &[Move], first: Player, winner: Option<Player>) {
+        self.policy.update(moves, first, winner);
+    }
+
+    fn reset(&self) {
+        self.policy.reset();
+    }
+}
+
+/// Policy named `name`: "uniform", "decisive", "anti_decisive",
+/// "small_board", "mast" or "lgr", mixed with uniform moves if `epsilon` is
+/// positive. `temperature` is the Gibbs sampling temperature of MAST.
+pub fn from_name(name: &str, epsilon: f64, temperature: f32) -> PyResult<Arc<dyn RolloutPolicy>> {
+    if !(0.0..=1.0).contains(&epsilon) {
+        return Err(PyValueError::new_err("rollout epsilon must be between 0 and 1"));
+    }
+    let policy: Box<dyn RolloutPolicy> = match name {
+        "uniform" => Box::new(Uniform),
+        "decisive" => Box::new(Decisive { anti: false }),
+        "anti_decisive" => Box::new(Decisive { anti: true }),
+        "small_board" => Box::new(SmallBoardAware),
+        "mast" if temperature <= 0.0 =>
+            return Err(PyValueError::new_err("MAST temperature must be greater than 0")),
+        "mast" => Box::new(Mast::new(temperature)),
+        "lgr" => Box::new(LastGoodReply::new()),
+        _ => return Err(PyValueError::new_err(format!(
+            "unknown rollout policy '{}', expected 'uniform', 'decisive', 'anti_decisive', \
+            'small_board', 'mast' or 'lgr'",
+            name))),
+    };
+    Ok(if epsilon > 0.0 {
+        Arc::new(EpsilonMixture { epsilon, policy })
+    } else {
+        Arc::from(policy)
+    })
+}
+
+/// Plays moves chosen by `policy` until the game is over or `max_plies`
+/// moves have been played, recording them in `playout`. Returns the
+/// position where it stopped.
+pub fn rollout(
+    mut state: Game,
+    policy: &dyn RolloutPolicy,
+    rng: &mut StdRng,
+    playout: &mut Vec<Move>,
+    max_plies: Option<u32>,
+) -> Game {
+    let mut plies = 0;
+    while !state.is_over() && max_plies.is_none_or(|max| plies < max) {
+        let moves = state.valid_moves();
+        let m = policy.choose(&state, &moves, rng);
+        state.make_move(m);
+        playout.push(m);
+        plies += 1;
+    }
+    state
+}
+
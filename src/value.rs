@@ -0,0 +1,119 @@
+use std::fmt::Debug;
+use std::sync::Arc;
+use pyo3::prelude::*;
+use pyo3::exceptions::PyValueError;
+use crate::env::create_observation;
+use crate::game::{Game, Player};
+
+/// Scores positions where a truncated rollout stops.
+pub trait ValueFunction: Send + Sync + Debug {
+    /// Expected reward, between 0 and 1, of the player to move in `state`.
+    /// Fails if a Python callable does.
+    fn value(&self, state: &Game) -> PyResult<f32>;
+}
+
+/// The 8 lines of a 3x3 board.
+const LINES: [[(usize, usize); 3]; 8] = [
+    [(0, 0), (0, 1), (0, 2)],
+    [(1, 0), (1, 1), (1, 2)],
+    [(2, 0), (2, 1), (2, 2)],
+    [(0, 0), (1, 0), (2, 0)],
+    [(0, 1), (1, 1), (2, 1)],
+    [(0, 2), (1, 2), (2, 2)],
+    [(0, 0), (1, 1), (2, 2)],
+    [(0, 2), (1, 1), (2, 0)],
+];
+
+/// Hand-made evaluation. A small board is worth 1 to the player who won it
+/// and, while undecided, a little for every line of it the player still
+/// holds alone. The meta lines the opponent cannot complete any more are
+/// then scored by the square of their worth, so that two boards on a line
+/// count for more than two boards apart.
+#[derive(Debug)]
+pub struct Heuristic;
+
+impl Heuristic {
+    /// How much the small boards are worth to `player`.
+    fn boards(state: &Game, player: Player) -> [[f32; 3]; 3] {
+        let mut worth = [[0.0; 3]; 3];
+        for (i, row) in worth.iter_mut().enumerate() {
+            for (j, worth) in row.iter_mut().enumerate() {
+                let board = state.small_board((i as u8, j as u8));
+                *worth = match board.get_winner() {
+                    Some(winner) => if winner == player { 1.0 } else { 0.0 },
+                    None if board.is_over() => 0.0,
+                    None => {
+                        let open: f32 = LINES.iter()
+                            .filter_map(|line| {
+                                let cells = line.map(|(k, l)| board.at((k as u8, l as u8)));
+                                let mine = cells.iter().filter(|&&cell| cell == Some(player)).count();
+                                (mine == cells.iter().flatten().count()).then_some(mine)
+                            })
+                            .map(|mine| [0.0, 0.05, 0.2, 0.0][mine])
+                            .sum();
+                        open.min(0.9)
+                    }
+                };
+            }
+        }
+        worth
+    }
+
+    fn score(state: &Game, player: Player) -> f32 {
+        let mine = Self::boards(state, player);
+        let blocked = |(i, j): (usize, usize)| {
+            let board = state.small_board((i as u8, j as u8));
+            board.is_over() && board.get_winner() != Some(player)
+        };
+        LINES.iter()
+            .filter(|line| !line.iter().any(|&cell| blocked(cell)))
+            .map(|line| line.iter().map(|&(i, j)| mine[i][j]).sum::<f32>().powi(2))
+            .sum()
+    }
+}
+
+impl ValueFunction for Heuristic {
+    fn value(&self, state: &Game) -> PyResult<f32> {
+        let player = state.current_player();
+        let advantage = Self::score(state, player) - Self::score(state, player.other());
+        Ok(1.0 / (1.0 + (-0.5 * advantage).exp()))
+    }
+}
+
+/// Python callable taking the observation of a position, as returned by
+/// `UTTTEnvImpl.step`, and returning the expected reward of the player to
+/// move between 0 and 1. Any other value raises `ValueError`. The GIL is
+/// taken for every call.
+#[derive(Debug)]
+pub struct PyValueFunction {
+    function: PyObject,
+}
+
+impl ValueFunction for PyValueFunction {
+    fn value(&self, state: &Game) -> PyResult<f32> {
+        let value = Python::with_gil(|py| {
+            self.function
+                .call1(py, (create_observation(state),))?
+                .extract::<f32>(py)
+        })?;
+        if !(0.0..=1.0).contains(&value) {
+            return Err(PyValueError::new_err(format!(
+                "value function returned {}, expected a number between 0 and 1", value)));
+        }
+        Ok(value)
+    }
+}
+
+/// Value function given from Python: "heuristic" or a callable. `None`
+/// is the heuristic.
+pub fn from_py(value_function: Option<&PyAny>) -> PyResult<Arc<dyn ValueFunction>> {
+    match value_function {
+        None => Ok(Arc::new(Heuristic)),
+        Some(function) if function.is_callable() => Ok(Arc::new(PyValueFunction { function: function.into() })),
+        Some(name) => match name.extract::<&str>() {
+            Ok("heuristic") => Ok(Arc::new(Heuristic)),
+            _ => Err(PyValueError::new_err(format!(
+                "unknown value function {}, expected 'heuristic' or a callable", name))),
+        },
+    }
+}